@@ -0,0 +1,533 @@
+use aws_sdk_ssm::Client as ssm_client;
+use chrono::{Duration, NaiveDate, Utc};
+use lambda_http::{Error, Request, Response};
+use serde::Deserialize;
+use shared::apimodels::ApiResponse;
+use shared::config::Config;
+use shared::error::ApiError;
+use shared::fetch::Fetcher;
+use shared::persistencemodels::*;
+use shared::storage::{DocumentStore, DocumentStoreExt, StorageError};
+use shared::validation::{replace_snapshot, write_rendered};
+use std::collections::HashMap;
+
+/// NeoWs rejects feed requests spanning more than seven days
+const MAX_FEED_DAYS: i64 = 7;
+
+/// Inclusive range of days to collect, e.g. for backfilling after an outage
+#[derive(Debug, PartialEq)]
+struct DateRange {
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+}
+
+/// Range as supplied by the invoking event, where either end may be omitted
+#[derive(Debug, Default, Deserialize)]
+struct DateRangeRequest {
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+}
+
+impl DateRange {
+    fn today() -> Self {
+        let today = Utc::now().date_naive();
+        DateRange {
+            start_date: today,
+            end_date: today,
+        }
+    }
+
+    /// Resolve the range from the event, then `START_DATE`/`END_DATE`, then today.
+    /// A missing end defaults to today and a missing start to the end date.
+    fn from_request(event: &Request, config: &Config) -> Result<Self, Error> {
+        let requested: DateRangeRequest = if event.body().is_empty() {
+            DateRangeRequest::default()
+        } else {
+            serde_json::from_slice(event.body())?
+        };
+
+        let end_date = match requested.end_date {
+            Some(date) => date,
+            None => config.neo_end_date.unwrap_or(Self::today().end_date),
+        };
+        let start_date = match requested.start_date {
+            Some(date) => date,
+            None => config.neo_start_date.unwrap_or(end_date),
+        };
+
+        if start_date > end_date {
+            return Err(Error::from(format!(
+                "start_date {} is after end_date {}",
+                start_date, end_date
+            )));
+        }
+
+        Ok(DateRange {
+            start_date,
+            end_date,
+        })
+    }
+
+    /// Whether the range ends before today, so it fills in past days rather
+    /// than collecting the latest feed
+    fn is_backfill(&self) -> bool {
+        self.end_date < Self::today().end_date
+    }
+
+    fn contains(&self, date: NaiveDate) -> bool {
+        self.start_date <= date && date <= self.end_date
+    }
+
+    fn days(&self) -> i64 {
+        (self.end_date - self.start_date).num_days() + 1
+    }
+
+    /// Number of feed requests needed to cover the range
+    fn window_count(&self) -> i64 {
+        (self.days() + MAX_FEED_DAYS - 1) / MAX_FEED_DAYS
+    }
+
+    fn first_window_end(&self) -> NaiveDate {
+        let window_end = self.start_date + Duration::days(MAX_FEED_DAYS - 1);
+        window_end.min(self.end_date)
+    }
+}
+
+async fn retrieve_api_key(config: &Config) -> Result<String, Error> {
+    if let Some(key) = &config.nasa_api_key {
+        return Ok(key.clone());
+    }
+
+    let secrets_client = ssm_client::new(&aws_config::load_from_env().await);
+    let location = &config.nasa_key_location;
+    let key_val = secrets_client
+        .get_parameter()
+        .name(location)
+        .with_decryption(true)
+        .send()
+        .await
+        .map_err(|err| {
+            ApiError::Misconfigured(format!(
+                "Could not read the NASA API key from SSM parameter {}: {}",
+                location, err
+            ))
+        })?
+        .parameter
+        .and_then(|parameter| parameter.value)
+        .ok_or_else(|| {
+            ApiError::Misconfigured(format!(
+                "SSM parameter {} has no value, set NASA_API_KEY or store the key there",
+                location
+            ))
+        })?;
+
+    Ok(key_val)
+}
+
+async fn retrieve_page(fetcher: &Fetcher, url: &str) -> Result<ApiResponse, Error> {
+    let data = fetcher.get_json(url).await?;
+
+    Ok(data)
+}
+
+/// Latest day present in a feed response
+fn last_day(response: &ApiResponse) -> Option<NaiveDate> {
+    response
+        .near_earth_objects
+        .keys()
+        .filter_map(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+        .max()
+}
+
+/// Fetch the range one feed window at a time, following `links.next` until
+/// the end date is covered
+async fn retrieve_data(
+    fetcher: &Fetcher,
+    feed_url: &str,
+    key_val: &str,
+    range: &DateRange,
+) -> Result<ApiResponse, Error> {
+    let mut url = format!(
+        "{}?start_date={}&end_date={}&api_key={}",
+        feed_url,
+        range.start_date,
+        range.first_window_end(),
+        key_val
+    );
+    let mut pages = Vec::new();
+
+    for _ in 0..range.window_count() {
+        let page = retrieve_page(fetcher, &url).await?;
+        let next = page.links.next.clone();
+        let covered = last_day(&page).is_none_or(|day| day >= range.end_date);
+        pages.push(page);
+
+        match next {
+            // NeoWs hands out http links, follow them over https like the first request
+            Some(next) if !covered && feed_url.starts_with("https://") => {
+                url = next.replace("http://", "https://")
+            }
+            Some(next) if !covered => url = next,
+            _ => break,
+        }
+    }
+
+    merge_pages(pages, range)
+}
+
+/// Combine feed windows into one response holding only the requested days
+fn merge_pages(pages: Vec<ApiResponse>, range: &DateRange) -> Result<ApiResponse, Error> {
+    let mut pages = pages.into_iter();
+    let mut merged = pages.next().ok_or("No NEO feed pages were retrieved")?;
+
+    for page in pages {
+        merged.near_earth_objects.extend(page.near_earth_objects);
+    }
+
+    merged.near_earth_objects.retain(|day, _| {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d").is_ok_and(|day| range.contains(day))
+    });
+    merged.element_count = merged
+        .near_earth_objects
+        .values()
+        .map(|objects| objects.len() as i32)
+        .sum();
+
+    Ok(merged)
+}
+
+async fn convert_to_storage(response: ApiResponse) -> Result<NearEarthObjectModel, Error> {
+    let mut days: Vec<_> = response.near_earth_objects.into_iter().collect();
+    days.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let near_earth_objects = days
+        .into_iter()
+        .flat_map(|(_, objects)| objects)
+        .map(NearEarthObject::try_from)
+        .collect::<Result<_, _>>()?;
+
+    Ok(NearEarthObjectModel {
+        links: response.links.into(),
+        element_count: response.element_count,
+        near_earth_objects,
+        updated_date_time: Utc::now().to_string(),
+    })
+}
+
+/// Split a feed response into one response per day, in date order
+fn split_by_day(response: ApiResponse) -> Vec<(String, ApiResponse)> {
+    let mut days: Vec<_> = response
+        .near_earth_objects
+        .into_iter()
+        .map(|(day, objects)| {
+            let day_response = ApiResponse {
+                links: response.links.clone(),
+                element_count: objects.len() as i32,
+                near_earth_objects: HashMap::from([(day.clone(), objects)]),
+            };
+            (day, day_response)
+        })
+        .collect();
+
+    days.sort_by(|a, b| a.0.cmp(&b.0));
+    days
+}
+
+/// Combine the per-day models back into the single "latest" document
+fn combine_days(days: Vec<NearEarthObjectModel>) -> Result<NearEarthObjectModel, Error> {
+    let mut days = days.into_iter();
+    let mut combined = days.next().ok_or("No near earth objects were retrieved")?;
+
+    for day in days {
+        combined.element_count += day.element_count;
+        combined.near_earth_objects.extend(day.near_earth_objects);
+    }
+
+    Ok(combined)
+}
+
+/// Write each day to its `neo/YYYY/MM/DD.json` snapshot, gzip-compressed when
+/// `compressed`, and record it in the archive index
+async fn write_archive(
+    store: &dyn DocumentStore,
+    days: &[(String, NearEarthObjectModel)],
+    compressed: bool,
+) -> Result<(), Error> {
+    let mut index = match store.get(NEO_ARCHIVE_INDEX_KEY).await {
+        Ok(index) => index,
+        Err(StorageError::NotFound(_)) => NearEarthObjectArchiveIndex::default(),
+        Err(err) => return Err(err.into()),
+    };
+
+    for (day, data) in days {
+        let key = neo_archive_key(day).ok_or_else(|| format!("Unexpected feed date {}", day))?;
+        if compressed {
+            store.put_compressed(&key, data).await?;
+        } else {
+            store.put(&key, data).await?;
+        }
+        index.upsert(NearEarthObjectArchiveDay {
+            date: day.clone(),
+            key,
+            element_count: data.element_count,
+        });
+    }
+
+    index.updated_date_time = Utc::now().to_string();
+    store.put(NEO_ARCHIVE_INDEX_KEY, &index).await?;
+
+    Ok(())
+}
+
+pub async fn function_handler(
+    store: &dyn DocumentStore,
+    config: &Config,
+    event: Request,
+) -> Result<Response<String>, Error> {
+    let range = DateRange::from_request(&event, config)?;
+    log::info!(
+        "Collecting near earth objects from {} to {}",
+        range.start_date,
+        range.end_date
+    );
+    let key_val = retrieve_api_key(config).await?;
+    let fetcher = Fetcher::new(config.retry_policy());
+    let data = retrieve_data(&fetcher, &config.neo_feed_url, &key_val, &range).await?;
+    let resp = Response::new("".to_string());
+
+    let mut days = Vec::new();
+    for (day, day_data) in split_by_day(data) {
+        days.push((day, convert_to_storage(day_data).await?));
+    }
+
+    // Every day passed its own checks, so archive them even when the latest
+    // document below is quarantined
+    if config.neo_archive_enabled {
+        write_archive(store, &days, config.compress_documents).await?;
+    }
+
+    // A backfill only fills in the archive, the latest document stays the
+    // most recent feed
+    if range.is_backfill() {
+        if !config.neo_archive_enabled {
+            log::warn!("The NEO archive is disabled, so the backfilled days were not stored");
+        }
+        return Ok(resp);
+    }
+
+    let converted_data = combine_days(days.iter().map(|(_, data)| data.clone()).collect())?;
+    let policy = config.snapshot_policy(config.neo_max_change_ratio);
+    replace_snapshot(store, &config.neo_file, &converted_data, &policy).await?;
+    write_rendered(
+        store,
+        &config.neo_file,
+        || NearEarthObjectApiResponse::unfiltered(&converted_data),
+        &policy,
+    )
+    .await?;
+
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use shared::testing::{StubResponse, StubServer};
+
+    const FEED_FIXTURE: &str = include_str!("../fixtures/neo_feed.json");
+
+    async fn feed_server() -> StubServer {
+        StubServer::builder()
+            .respond("/neo/rest/v1/feed", StubResponse::json(FEED_FIXTURE))
+            .start()
+            .await
+    }
+
+    #[tokio::test]
+    async fn retrieve_data_reads_the_feed() {
+        let server = feed_server().await;
+        let range = range("2023-11-01", "2023-11-02");
+
+        let data = retrieve_data(
+            &Fetcher::default(),
+            &server.url("/neo/rest/v1/feed"),
+            "DEMO_KEY",
+            &range,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(data.element_count, 3);
+        assert_eq!(data.near_earth_objects["2023-11-01"].len(), 2);
+        assert_eq!(
+            server.requests(),
+            vec!["/neo/rest/v1/feed?start_date=2023-11-01&end_date=2023-11-02&api_key=DEMO_KEY"]
+        );
+    }
+
+    #[tokio::test]
+    async fn convert_to_storage_orders_days_and_parses_numbers() {
+        let response: ApiResponse = serde_json::from_str(FEED_FIXTURE).unwrap();
+
+        let model = convert_to_storage(response).await.unwrap();
+
+        let ids: Vec<_> = model
+            .near_earth_objects
+            .iter()
+            .map(|neo| neo.id.as_str())
+            .collect();
+        assert_eq!(ids[2], "3843281");
+        assert_eq!(model.element_count, 3);
+        assert!(model
+            .near_earth_objects
+            .iter()
+            .all(|neo| neo.nasa_jpl_url.starts_with("https://")));
+        assert_eq!(
+            model.near_earth_objects[0].close_approach_data[0]
+                .miss_distance
+                .lunar,
+            97.3073445128
+        );
+    }
+
+    /// Refresh the recorded fixture from the live API: `cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn record_feed_fixture() {
+        let url = format!(
+            "{}?start_date=2023-11-01&end_date=2023-11-02&api_key=DEMO_KEY",
+            Config::default().neo_feed_url
+        );
+        let body = Fetcher::default().get_text(&url).await.unwrap();
+        std::fs::write(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/neo_feed.json"),
+            body,
+        )
+        .unwrap();
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn range(start: &str, end: &str) -> DateRange {
+        DateRange {
+            start_date: date(start),
+            end_date: date(end),
+        }
+    }
+
+    fn page(days: &[&str]) -> ApiResponse {
+        let near_earth_objects: serde_json::Map<String, serde_json::Value> = days
+            .iter()
+            .map(|day| (day.to_string(), serde_json::json!([])))
+            .collect();
+
+        serde_json::from_value(serde_json::json!({
+            "links": { "next": null, "prev": null, "self": null },
+            "element_count": 0,
+            "near_earth_objects": near_earth_objects,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn single_day_needs_one_window() {
+        let range = range("2023-11-01", "2023-11-01");
+        assert_eq!(range.window_count(), 1);
+        assert_eq!(range.first_window_end(), date("2023-11-01"));
+    }
+
+    #[test]
+    fn month_is_chunked_into_seven_day_windows() {
+        let range = range("2023-10-01", "2023-10-31");
+        assert_eq!(range.window_count(), 5);
+        assert_eq!(range.first_window_end(), date("2023-10-07"));
+    }
+
+    #[test]
+    fn range_is_read_from_event_body() {
+        let event = Request::new(r#"{"start_date":"2023-10-01","end_date":"2023-10-31"}"#.into());
+        assert_eq!(
+            DateRange::from_request(&event, &Config::default()).unwrap(),
+            range("2023-10-01", "2023-10-31")
+        );
+    }
+
+    #[test]
+    fn range_falls_back_to_configured_dates() {
+        let config = Config {
+            neo_start_date: Some(date("2023-10-01")),
+            neo_end_date: Some(date("2023-10-07")),
+            ..Config::default()
+        };
+        assert_eq!(
+            DateRange::from_request(&Request::default(), &config).unwrap(),
+            range("2023-10-01", "2023-10-07")
+        );
+    }
+
+    #[test]
+    fn ranges_ending_before_today_are_backfills() {
+        assert!(range("2023-10-01", "2023-10-07").is_backfill());
+        assert!(!DateRange::today().is_backfill());
+
+        let today = Utc::now().date_naive();
+        let last_week = DateRange {
+            start_date: today - Duration::days(7),
+            end_date: today,
+        };
+        assert!(!last_week.is_backfill());
+    }
+
+    #[test]
+    fn reversed_range_is_rejected() {
+        let event = Request::new(r#"{"start_date":"2023-10-31","end_date":"2023-10-01"}"#.into());
+        assert!(DateRange::from_request(&event, &Config::default()).is_err());
+    }
+
+    #[test]
+    fn merge_keeps_only_requested_days() {
+        let merged = merge_pages(
+            vec![
+                page(&["2023-10-01", "2023-10-02"]),
+                page(&["2023-10-03", "2023-10-04"]),
+            ],
+            &range("2023-10-01", "2023-10-03"),
+        )
+        .unwrap();
+
+        let mut days: Vec<_> = merged.near_earth_objects.keys().cloned().collect();
+        days.sort();
+        assert_eq!(days, vec!["2023-10-01", "2023-10-02", "2023-10-03"]);
+    }
+
+    #[tokio::test]
+    async fn archive_writes_each_day_and_indexes_it() {
+        let store = shared::storage::MemoryStore::new();
+        let mut days = Vec::new();
+        for (day, data) in split_by_day(page(&["2023-11-02", "2023-11-01"])) {
+            days.push((day, convert_to_storage(data).await.unwrap()));
+        }
+
+        write_archive(&store, &days, true).await.unwrap();
+
+        assert_eq!(
+            store.list("neo/").await.unwrap(),
+            vec![
+                "neo/2023/11/01.json",
+                "neo/2023/11/02.json",
+                NEO_ARCHIVE_INDEX_KEY
+            ]
+        );
+        let index: NearEarthObjectArchiveIndex = store.get(NEO_ARCHIVE_INDEX_KEY).await.unwrap();
+        let dates: Vec<_> = index.days.iter().map(|day| day.date.as_str()).collect();
+        assert_eq!(dates, vec!["2023-11-01", "2023-11-02"]);
+
+        let raw = store.get_bytes("neo/2023/11/01.json").await.unwrap();
+        assert_eq!(raw[..2], [0x1f, 0x8b]);
+        let day: NearEarthObjectModel = store.get("neo/2023/11/01.json").await.unwrap();
+        assert_eq!(day.element_count, index.days[0].element_count);
+    }
+}
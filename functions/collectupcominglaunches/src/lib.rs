@@ -0,0 +1,319 @@
+use aws_sdk_ssm::Client as ssm_client;
+use chrono::{SecondsFormat, Utc};
+use lambda_http::{Error, Request, Response};
+use shared::config::Config;
+use shared::error::ApiError;
+use shared::fetch::Fetcher;
+use shared::history::{diff_launches, LaunchChange, LaunchChangeLog};
+use shared::persistencemodels::{UpcomingLaunches, UpcomingLaunchesResponse};
+use shared::storage::{DocumentStore, DocumentStoreExt, StorageError};
+use shared::validation::{replace_snapshot, write_rendered};
+
+async fn retrieve_api_key(config: &Config) -> Result<String, Error> {
+    if let Some(key) = &config.launch_api_key {
+        return Ok(key.clone());
+    }
+
+    let secrets_client = ssm_client::new(&aws_config::load_from_env().await);
+    let location = &config.launch_key_location;
+    let key_val = secrets_client
+        .get_parameter()
+        .name(location)
+        .with_decryption(true)
+        .send()
+        .await
+        .map_err(|err| {
+            ApiError::Misconfigured(format!(
+                "Could not read the launch API key from SSM parameter {}: {}",
+                location, err
+            ))
+        })?
+        .parameter
+        .and_then(|parameter| parameter.value)
+        .ok_or_else(|| {
+            ApiError::Misconfigured(format!(
+                "SSM parameter {} has no value, set LAUNCH_API_KEY or store the key there",
+                location
+            ))
+        })?;
+
+    Ok(key_val)
+}
+
+async fn retrieve_page(
+    fetcher: &Fetcher,
+    launches_url: &str,
+    key: &str,
+    page: u64,
+) -> Result<UpcomingLaunches, Error> {
+    log::info!("Retrieving launches page {}", page);
+    let url = format!("{}?key={}&page={}", launches_url, key, page);
+    let data = fetcher.get_json(&url).await?;
+
+    Ok(data)
+}
+
+/// Fetch every page of upcoming launches, starting from the first page and
+/// following `last_page` until the listing is exhausted
+async fn retrieve_data(
+    fetcher: &Fetcher,
+    launches_url: &str,
+    key: &str,
+) -> Result<Vec<UpcomingLaunches>, Error> {
+    let first_page = retrieve_page(fetcher, launches_url, key, 1).await?;
+    let last_page = first_page.last_page;
+    let mut pages = vec![first_page];
+
+    for page in 2..=last_page {
+        pages.push(retrieve_page(fetcher, launches_url, key, page).await?);
+    }
+
+    Ok(pages)
+}
+
+/// Merge the individual API pages into the single document the read side serves
+fn merge_pages(pages: Vec<UpcomingLaunches>) -> Result<UpcomingLaunches, Error> {
+    let mut pages = pages.into_iter();
+    let mut merged = pages.next().ok_or("No launch pages were retrieved")?;
+
+    for page in pages {
+        merged.valid_auth &= page.valid_auth;
+        merged.result.extend(page.result);
+    }
+
+    merged.count = merged.result.len() as u32;
+    merged.limit = merged.count;
+    merged.last_page = 1;
+    merged.generated_at = Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+
+    Ok(merged)
+}
+
+/// The launches currently stored, if there is a readable document
+async fn read_previous(
+    store: &dyn DocumentStore,
+    config: &Config,
+) -> Result<Option<UpcomingLaunches>, Error> {
+    match store.get(&config.launches_file).await {
+        Ok(previous) => Ok(Some(previous)),
+        Err(StorageError::NotFound(_)) | Err(StorageError::Serialization(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Append schedule changes to the change log
+async fn record_changes(
+    store: &dyn DocumentStore,
+    config: &Config,
+    changes: Vec<LaunchChange>,
+) -> Result<(), Error> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut log: LaunchChangeLog = match store.get(&config.launch_changes_file).await {
+        Ok(log) => log,
+        Err(StorageError::NotFound(_)) => LaunchChangeLog::default(),
+        Err(err) => return Err(err.into()),
+    };
+    log::info!("Recording {} launch schedule changes", changes.len());
+    log.changes.extend(changes);
+    store.put(&config.launch_changes_file, &log).await?;
+
+    Ok(())
+}
+
+pub async fn function_handler(
+    store: &dyn DocumentStore,
+    config: &Config,
+    _event: Request,
+) -> Result<Response<String>, Error> {
+    let key = retrieve_api_key(config).await?;
+    let fetcher = Fetcher::new(config.retry_policy());
+    let pages = retrieve_data(&fetcher, &config.launches_url, &key).await?;
+    let launches = merge_pages(pages)?;
+    let previous = read_previous(store, config).await?;
+    let changes = diff_launches(previous.as_ref(), &launches, Utc::now());
+    log::info!("Writing {} launches", launches.count);
+    let policy = config.snapshot_policy(config.launches_max_change_ratio);
+    replace_snapshot(store, &config.launches_file, &launches, &policy).await?;
+    write_rendered(
+        store,
+        &config.launches_file,
+        || UpcomingLaunchesResponse::unfiltered(&launches),
+        &policy,
+    )
+    .await?;
+    record_changes(store, config, changes).await?;
+
+    Ok(Response::new("".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::history::LaunchChangeKind;
+    use shared::storage::{is_gzip, rendered_key, MemoryStore};
+    use shared::testing::{StubResponse, StubServer};
+
+    const PAGE_1_FIXTURE: &str = include_str!("../fixtures/launches_page_1.json");
+    const PAGE_2_FIXTURE: &str = include_str!("../fixtures/launches_page_2.json");
+
+    fn page(ids: &[u64], last_page: u64) -> UpcomingLaunches {
+        let result = ids
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "id": id,
+                    "cospar_id": null,
+                    "sort_date": "1700000000",
+                    "name": format!("Launch {}", id),
+                    "provider": { "id": 1, "name": "SpaceX", "slug": "spacex" },
+                    "vehicle": { "id": 1, "name": "Falcon 9", "company_id": 1, "slug": "falcon-9", "pad": null },
+                    "pad": null,
+                    "missions": [],
+                    "mission_description": "",
+                    "launch_description": "",
+                    "win_open": null,
+                    "t0": null,
+                    "win_close": null,
+                    "date_str": "Nov 14",
+                    "tags": [],
+                    "slug": format!("launch-{}", id),
+                    "weather_summary": null,
+                    "weather_temp": 0,
+                    "weather_condition": null,
+                    "weather_wind_mph": null,
+                    "weather_icon": null,
+                    "weather_updated": null,
+                    "quick_text": null,
+                    "suborbital": false,
+                    "modified": "2023-11-01T00:00:00+00:00"
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::from_value(serde_json::json!({
+            "valid_auth": true,
+            "count": ids.len(),
+            "limit": 25,
+            "total": 3,
+            "last_page": last_page,
+            "result": result,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn merge_pages_combines_results_in_order() {
+        let merged = merge_pages(vec![page(&[1, 2], 2), page(&[3], 2)]).unwrap();
+        let ids: Vec<u64> = merged.result.iter().map(|launch| launch.id).collect();
+
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(merged.count, 3);
+        assert_eq!(merged.total, 3);
+        assert_eq!(merged.last_page, 1);
+        assert!(merged.generated_at.is_some());
+    }
+
+    #[test]
+    fn merge_pages_requires_at_least_one_page() {
+        assert!(merge_pages(Vec::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn retrieve_data_follows_every_page() {
+        let server = StubServer::builder()
+            .respond("/json/launches", StubResponse::json(PAGE_1_FIXTURE))
+            .respond("/json/launches", StubResponse::json(PAGE_2_FIXTURE))
+            .start()
+            .await;
+
+        let pages = retrieve_data(&Fetcher::default(), &server.url("/json/launches"), "key")
+            .await
+            .unwrap();
+        let merged = merge_pages(pages).unwrap();
+
+        let ids: Vec<u64> = merged.result.iter().map(|launch| launch.id).collect();
+        assert_eq!(ids, vec![4451, 4420, 4375]);
+        assert_eq!(
+            server.requests(),
+            vec![
+                "/json/launches?key=key&page=1",
+                "/json/launches?key=key&page=2"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn handler_records_schedule_changes() {
+        let server = StubServer::builder()
+            .respond("/json/launches", StubResponse::json(PAGE_1_FIXTURE))
+            .respond("/json/launches", StubResponse::json(PAGE_2_FIXTURE))
+            .start()
+            .await;
+        let config = Config {
+            launches_url: server.url("/json/launches"),
+            launch_api_key: Some("key".to_string()),
+            compress_documents: true,
+            ..Config::default()
+        };
+
+        let mut previous: UpcomingLaunches = serde_json::from_str(PAGE_1_FIXTURE).unwrap();
+        previous.result[0].t0 = Some("2023-11-02T08:45:00Z".to_string());
+        previous.result[0].win_open = previous.result[0].t0.clone();
+        let store = MemoryStore::new();
+        store.put(&config.launches_file, &previous).await.unwrap();
+
+        function_handler(&store, &config, Request::default())
+            .await
+            .unwrap();
+
+        let log: LaunchChangeLog = store.get(&config.launch_changes_file).await.unwrap();
+        let changes: Vec<(u64, LaunchChangeKind)> = log
+            .changes
+            .iter()
+            .map(|change| (change.id, change.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (4451, LaunchChangeKind::Scrubbed),
+                (4375, LaunchChangeKind::Added)
+            ]
+        );
+
+        let rendered = store
+            .get_bytes(&rendered_key(&config.launches_file))
+            .await
+            .unwrap();
+        assert!(is_gzip(&rendered));
+        let rendered: UpcomingLaunchesResponse = store
+            .get(&rendered_key(&config.launches_file))
+            .await
+            .unwrap();
+        assert_eq!(rendered.filtered_count, rendered.launches.result.len());
+    }
+
+    /// Refresh the recorded fixtures from the live API, needs `LAUNCH_API_KEY`:
+    /// `cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn record_launch_fixtures() {
+        let config = Config::from_env().unwrap();
+        let key = config.launch_api_key.unwrap();
+        for page in 1..=2 {
+            let url = format!("{}?key={}&page={}", config.launches_url, key, page);
+            let body = Fetcher::default().get_text(&url).await.unwrap();
+            std::fs::write(
+                format!(
+                    "{}/fixtures/launches_page_{}.json",
+                    env!("CARGO_MANIFEST_DIR"),
+                    page
+                ),
+                body,
+            )
+            .unwrap();
+        }
+    }
+}
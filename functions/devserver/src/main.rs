@@ -0,0 +1,311 @@
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Method, Server};
+use lambda_http::{Body, Error, Request, RequestExt};
+use log::LevelFilter;
+use shared::config::Config;
+use shared::error::{ApiError, ProblemDetails, PROBLEM_JSON};
+use shared::storage::{CachedStore, DocumentStore, FileStore};
+use simple_logger::SimpleLogger;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+const DEFAULT_STORAGE_DIR: &str = "data";
+const DEFAULT_PORT: u16 = 3000;
+
+#[derive(Debug, PartialEq)]
+enum Collector {
+    People,
+    Launches,
+    NearEarthObjects,
+    Notifications,
+}
+
+/// The lambda a request is dispatched to, mirroring the API Gateway resources
+#[derive(Debug, PartialEq)]
+enum Route {
+    People,
+    PeopleHistory,
+    Person { name: String },
+    Launches,
+    LaunchHistory { id: String },
+    NearEarthObjects { date: Option<String> },
+    OpenApi,
+    Collect(Collector),
+}
+
+fn route(method: &Method, path: &str) -> Option<Route> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (method, segments.as_slice()) {
+        (&Method::GET, ["people"]) => Some(Route::People),
+        (&Method::GET, ["people", "history"]) => Some(Route::PeopleHistory),
+        (&Method::GET, ["people", name]) => Some(Route::Person {
+            name: name.to_string(),
+        }),
+        (&Method::GET, ["launches"]) => Some(Route::Launches),
+        (&Method::GET, ["launches.ics"]) => Some(Route::Launches),
+        (&Method::GET, ["launches", id, "history"]) => {
+            Some(Route::LaunchHistory { id: id.to_string() })
+        }
+        (&Method::GET, ["neo"]) => Some(Route::NearEarthObjects { date: None }),
+        (&Method::GET, ["neo", date]) => Some(Route::NearEarthObjects {
+            date: Some(date.to_string()),
+        }),
+        (&Method::GET, ["openapi.json"]) => Some(Route::OpenApi),
+        (&Method::POST, ["collect", "people"]) => Some(Route::Collect(Collector::People)),
+        (&Method::POST, ["collect", "launches"]) => Some(Route::Collect(Collector::Launches)),
+        (&Method::POST, ["collect", "neo"]) => Some(Route::Collect(Collector::NearEarthObjects)),
+        (&Method::POST, ["collect", "notifications"]) => {
+            Some(Route::Collect(Collector::Notifications))
+        }
+        _ => None,
+    }
+}
+
+/// Turn the incoming request into the event API Gateway would have delivered
+async fn to_event(request: hyper::Request<hyper::Body>) -> Result<Request, hyper::Error> {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+
+    let mut query: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, value) in
+        url::form_urlencoded::parse(parts.uri.query().unwrap_or_default().as_bytes())
+    {
+        query
+            .entry(name.into_owned())
+            .or_default()
+            .push(value.into_owned());
+    }
+
+    let body = if body.is_empty() {
+        Body::Empty
+    } else {
+        Body::from(body.to_vec())
+    };
+
+    Ok(Request::from_parts(parts, body).with_query_string_parameters(query))
+}
+
+fn problem_response(problem: ProblemDetails) -> hyper::Response<hyper::Body> {
+    let body = serde_json::to_string(&problem).unwrap_or_default();
+
+    hyper::Response::builder()
+        .status(problem.status)
+        .header("content-type", PROBLEM_JSON)
+        .body(body.into())
+        .unwrap_or_default()
+}
+
+async fn dispatch(
+    store: &dyn DocumentStore,
+    config: &Config,
+    route: Route,
+    event: Request,
+) -> Result<hyper::Response<hyper::Body>, Error> {
+    let response = match route {
+        Route::People | Route::PeopleHistory => {
+            readpeople::function_handler(store, config, event).await?
+        }
+        Route::Person { name } => {
+            let event = event.with_path_parameters(HashMap::from([("name".to_string(), name)]));
+            readpeople::function_handler(store, config, event).await?
+        }
+        Route::Launches => readupcominglaunches::function_handler(store, config, event).await?,
+        Route::LaunchHistory { id } => {
+            let event = event.with_path_parameters(HashMap::from([("id".to_string(), id)]));
+            readupcominglaunches::function_handler(store, config, event).await?
+        }
+        Route::NearEarthObjects { date } => {
+            let event = match date {
+                Some(date) => {
+                    event.with_path_parameters(HashMap::from([("date".to_string(), date)]))
+                }
+                None => event,
+            };
+            retrievenearearthobjects::function_handler(store, config, event).await?
+        }
+        Route::OpenApi => readopenapi::function_handler(event).await?,
+        Route::Collect(collector) => {
+            let response = match collector {
+                Collector::People => {
+                    getpeopleinspacedata::function_handler(store, config, event).await?
+                }
+                Collector::Launches => {
+                    collectupcominglaunches::function_handler(store, config, event).await?
+                }
+                Collector::NearEarthObjects => {
+                    collectandstorenearearthobjects::function_handler(store, config, event).await?
+                }
+                Collector::Notifications => {
+                    dispatchnotifications::function_handler(store, config, event).await?
+                }
+            };
+            response.map(Body::from)
+        }
+    };
+
+    Ok(response.map(|body: Body| hyper::Body::from(body.to_vec())))
+}
+
+async fn handle(
+    store: &dyn DocumentStore,
+    config: &Config,
+    request: hyper::Request<hyper::Body>,
+) -> hyper::Response<hyper::Body> {
+    let (method, path) = (request.method().clone(), request.uri().path().to_string());
+    log::info!("{} {}", method, path);
+
+    let Some(route) = route(&method, &path) else {
+        let err = ApiError::NotFound(format!("No route for {} {}", method, path));
+        return problem_response(err.problem());
+    };
+
+    let result = match to_event(request).await {
+        Ok(event) => dispatch(store, config, route, event).await,
+        Err(err) => Err(err.into()),
+    };
+
+    result.unwrap_or_else(|err| {
+        log::error!("{} {} failed: {}", method, path, err);
+        problem_response(ProblemDetails {
+            problem_type: "about:blank",
+            title: "Internal Server Error",
+            status: 500,
+            detail: err.to_string(),
+        })
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    SimpleLogger::new()
+        .with_level(LevelFilter::Info)
+        .init()
+        .unwrap();
+
+    let config = Arc::new(Config::from_env()?);
+    let dir = config
+        .local_storage_dir
+        .clone()
+        .unwrap_or_else(|| DEFAULT_STORAGE_DIR.to_string());
+    let port = match env::var("PORT") {
+        Ok(port) => port.parse()?,
+        Err(_) => DEFAULT_PORT,
+    };
+    let store: Arc<dyn DocumentStore> = Arc::new(CachedStore::new(
+        FileStore::new(&dir),
+        config.document_cache_ttl,
+    ));
+
+    let make_service = make_service_fn(move |_| {
+        let (store, config) = (store.clone(), config.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let (store, config) = (store.clone(), config.clone());
+                async move { Ok::<_, Infallible>(handle(store.as_ref(), &config, request).await) }
+            }))
+        }
+    });
+
+    let address = SocketAddr::from(([127, 0, 0, 1], port));
+    log::info!("Serving documents from {} on http://{}", dir, address);
+    Server::bind(&address).serve(make_service).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::storage::{DocumentStoreExt, MemoryStore};
+
+    #[test]
+    fn routes_match_production_paths() {
+        assert_eq!(route(&Method::GET, "/people"), Some(Route::People));
+        assert_eq!(route(&Method::GET, "/launches/"), Some(Route::Launches));
+        assert_eq!(
+            route(&Method::GET, "/launches/4451/history"),
+            Some(Route::LaunchHistory {
+                id: "4451".to_string()
+            })
+        );
+        assert_eq!(
+            route(&Method::GET, "/people/history"),
+            Some(Route::PeopleHistory)
+        );
+        assert_eq!(
+            route(&Method::GET, "/people/Loral%20O'Hara"),
+            Some(Route::Person {
+                name: "Loral%20O'Hara".to_string()
+            })
+        );
+        assert_eq!(
+            route(&Method::GET, "/neo/2023-11-01"),
+            Some(Route::NearEarthObjects {
+                date: Some("2023-11-01".to_string())
+            })
+        );
+        assert_eq!(route(&Method::GET, "/openapi.json"), Some(Route::OpenApi));
+        assert_eq!(
+            route(&Method::POST, "/collect/neo"),
+            Some(Route::Collect(Collector::NearEarthObjects))
+        );
+        assert_eq!(
+            route(&Method::POST, "/collect/notifications"),
+            Some(Route::Collect(Collector::Notifications))
+        );
+        assert_eq!(route(&Method::POST, "/people"), None);
+        assert_eq!(route(&Method::GET, "/collect/people"), None);
+    }
+
+    #[tokio::test]
+    async fn serves_read_handlers_from_store() {
+        let store = MemoryStore::new();
+        store
+            .put(
+                &Config::default().people_file,
+                &serde_json::json!({
+                    "update_time": "2023-11-01 00:00:00 +0000",
+                    "people": [{ "name": "Jasmin Moghbeli", "craft": "ISS" }]
+                }),
+            )
+            .await
+            .unwrap();
+
+        let request = hyper::Request::get("/people")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let response = handle(&store, &Config::default(), request).await;
+
+        assert_eq!(response.status(), 200);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["people"][0]["name"], "Jasmin Moghbeli");
+    }
+
+    #[tokio::test]
+    async fn unknown_route_is_problem_json() {
+        let request = hyper::Request::get("/nope")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let response = handle(&MemoryStore::new(), &Config::default(), request).await;
+
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.headers()["content-type"], PROBLEM_JSON);
+    }
+
+    #[tokio::test]
+    async fn query_parameters_reach_the_handler() {
+        let request = hyper::Request::get("/neo?date=2023-11-01&hazardous=true")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let event = to_event(request).await.unwrap();
+
+        let query = event.query_string_parameters();
+        assert_eq!(query.first("date"), Some("2023-11-01"));
+        assert_eq!(query.first("hazardous"), Some("true"));
+    }
+}
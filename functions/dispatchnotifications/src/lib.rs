@@ -0,0 +1,445 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use lambda_http::{Error, Request, Response};
+use serde::de::DeserializeOwned;
+use shared::config::Config;
+use shared::fetch::Fetcher;
+use shared::history::{
+    parse_timestamp, LaunchChangeKind, LaunchChangeLog, PeopleEventKind, PeopleEventLog,
+};
+use shared::notify::{dispatch, Notification, NotificationKind, SubscriptionList};
+use shared::persistencemodels::{NearEarthObjectModel, UpcomingLaunches};
+use shared::storage::{DocumentStore, DocumentStoreExt, StorageError};
+use std::collections::BTreeMap;
+
+/// Events older than this when the function runs are not notified
+const LOOKBACK_HOURS: i64 = 24;
+
+fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// A stored document, or `None` while its collector has not written a
+/// readable one yet
+async fn read_optional<T: DeserializeOwned>(
+    store: &dyn DocumentStore,
+    key: &str,
+) -> Result<Option<T>, Error> {
+    match store.get(key).await {
+        Ok(document) => Ok(Some(document)),
+        Err(StorageError::NotFound(_)) | Err(StorageError::Serialization(_)) => {
+            log::info!("No readable {}, skipping its notifications", key);
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Upcoming Earth approaches of potentially hazardous objects closer than
+/// `max_lunar_distance`
+fn hazardous_approaches(
+    neos: &NearEarthObjectModel,
+    max_lunar_distance: f64,
+    now: DateTime<Utc>,
+) -> Vec<Notification> {
+    let mut notifications = Vec::new();
+    for neo in neos
+        .near_earth_objects
+        .iter()
+        .filter(|neo| neo.is_potentially_hazardous_asteroid)
+    {
+        for approach in &neo.close_approach_data {
+            if approach.orbiting_body != "Earth"
+                || approach.epoch_date_close_approach < now.timestamp_millis()
+                || approach.miss_distance.lunar > max_lunar_distance
+            {
+                continue;
+            }
+            notifications.push(Notification {
+                id: format!("neo:{}:{}", neo.id, approach.close_approach_date),
+                kind: NotificationKind::HazardousApproach,
+                occurred_at: approach.close_approach_date.clone(),
+                data: serde_json::json!({
+                    "id": neo.id,
+                    "name": neo.name,
+                    "close_approach_date": approach.close_approach_date,
+                    "miss_distance_lunar": approach.miss_distance.lunar,
+                    "miss_distance_kilometers": approach.miss_distance.kilometers,
+                    "kilometers_per_second": approach.relative_velocity.kilometers_per_second,
+                    "nasa_jpl_url": neo.nasa_jpl_url,
+                }),
+            });
+        }
+    }
+    notifications
+}
+
+/// One notification per collector run that saw people arrive or depart
+fn crew_changes(log: &PeopleEventLog, now: DateTime<Utc>) -> Vec<Notification> {
+    let since = now - Duration::hours(LOOKBACK_HOURS);
+    let mut runs: BTreeMap<&str, (Vec<serde_json::Value>, Vec<serde_json::Value>)> =
+        BTreeMap::new();
+    for event in &log.events {
+        if parse_timestamp(&event.timestamp).is_none_or(|at| at < since) {
+            continue;
+        }
+        let (arrived, departed) = runs.entry(&event.timestamp).or_default();
+        let person = serde_json::json!({ "name": event.name, "craft": event.craft });
+        match event.kind {
+            PeopleEventKind::Arrived => arrived.push(person),
+            PeopleEventKind::Departed => departed.push(person),
+        }
+    }
+
+    runs.into_iter()
+        .map(|(timestamp, (arrived, departed))| Notification {
+            id: format!("crew:{}", timestamp),
+            kind: NotificationKind::CrewChanged,
+            occurred_at: timestamp.to_string(),
+            data: serde_json::json!({ "arrived": arrived, "departed": departed }),
+        })
+        .collect()
+}
+
+/// Launches whose NET is within 24 hours, or within 1 hour; the NET is part
+/// of the id so a launch that slips is announced again
+fn imminent_launches(launches: &UpcomingLaunches, now: DateTime<Utc>) -> Vec<Notification> {
+    let mut notifications = Vec::new();
+    for launch in &launches.result {
+        let Some(net) = launch
+            .t0
+            .as_deref()
+            .or(launch.win_open.as_deref())
+            .and_then(|net| DateTime::parse_from_rfc3339(net).ok())
+            .map(|net| net.with_timezone(&Utc))
+        else {
+            continue;
+        };
+        let until = net - now;
+        let stage = if until <= Duration::zero() || until > Duration::hours(24) {
+            continue;
+        } else if until <= Duration::hours(1) {
+            "t-1h"
+        } else {
+            "t-24h"
+        };
+        notifications.push(Notification {
+            id: format!("launch:{}:{}:{}", launch.id, stage, rfc3339(net)),
+            kind: NotificationKind::LaunchImminent,
+            occurred_at: rfc3339(now),
+            data: serde_json::json!({
+                "id": launch.id,
+                "name": launch.name,
+                "stage": stage,
+                "net": rfc3339(net),
+                "provider": launch.provider.name,
+                "vehicle": launch.vehicle.name,
+            }),
+        });
+    }
+    notifications
+}
+
+/// Recently detected slips and scrubs
+fn launch_slips(log: &LaunchChangeLog, now: DateTime<Utc>) -> Vec<Notification> {
+    let since = now - Duration::hours(LOOKBACK_HOURS);
+    log.changes
+        .iter()
+        .filter(|change| {
+            matches!(
+                change.kind,
+                LaunchChangeKind::Slipped | LaunchChangeKind::Scrubbed
+            )
+        })
+        .filter(|change| {
+            DateTime::parse_from_rfc3339(&change.detected_at).is_ok_and(|at| at >= since)
+        })
+        .map(|change| {
+            let kind = match change.kind {
+                LaunchChangeKind::Scrubbed => "scrubbed",
+                _ => "slipped",
+            };
+            Notification {
+                id: format!("launch:{}:{}:{}", change.id, kind, change.detected_at),
+                kind: NotificationKind::LaunchSlipped,
+                occurred_at: change.detected_at.clone(),
+                data: serde_json::to_value(change).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Everything currently worth notifying about, from whichever documents exist
+async fn collect_notifications(
+    store: &dyn DocumentStore,
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Result<Vec<Notification>, Error> {
+    let mut notifications = Vec::new();
+
+    if let Some(neos) = read_optional(store, &config.neo_file).await? {
+        notifications.extend(hazardous_approaches(
+            &neos,
+            config.neo_alert_lunar_distance,
+            now,
+        ));
+    }
+    if let Some(log) = read_optional(store, &config.people_events_file).await? {
+        notifications.extend(crew_changes(&log, now));
+    }
+    if let Some(launches) = read_optional(store, &config.launches_file).await? {
+        notifications.extend(imminent_launches(&launches, now));
+    }
+    if let Some(log) = read_optional(store, &config.launch_changes_file).await? {
+        notifications.extend(launch_slips(&log, now));
+    }
+
+    Ok(notifications)
+}
+
+pub async fn function_handler(
+    store: &dyn DocumentStore,
+    config: &Config,
+    _event: Request,
+) -> Result<Response<String>, Error> {
+    let subscriptions: SubscriptionList = match store.get(&config.subscriptions_file).await {
+        Ok(subscriptions) => subscriptions,
+        Err(StorageError::NotFound(_)) => {
+            log::info!("No subscriptions in {}", config.subscriptions_file);
+            return Ok(Response::new("".to_string()));
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let now = Utc::now();
+    let notifications = collect_notifications(store, config, now).await?;
+    log::info!(
+        "Dispatching {} notifications to {} subscriptions",
+        notifications.len(),
+        subscriptions.subscriptions.len()
+    );
+
+    let fetcher = Fetcher::new(config.retry_policy());
+    let report = dispatch(
+        store,
+        &fetcher,
+        &config.notify_ledger_file,
+        &subscriptions.subscriptions,
+        &notifications,
+        now.timestamp() as u64,
+    )
+    .await?;
+    log::info!(
+        "Delivered {}, dead-lettered {}, skipped {} already delivered",
+        report.delivered,
+        report.dead_lettered,
+        report.skipped
+    );
+
+    Ok(Response::new("".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::notify::{Subscription, SIGNATURE_HEADER};
+    use shared::storage::MemoryStore;
+    use shared::testing::{StubResponse, StubServer};
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2023-11-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn neos(hazardous: bool, epoch_millis: i64, lunar: f64) -> NearEarthObjectModel {
+        let values =
+            serde_json::json!({ "estimated_diameter_min": 0.1, "estimated_diameter_max": 0.2 });
+        serde_json::from_value(serde_json::json!({
+            "links": { "next": null, "prev": null, "this": null },
+            "element_count": 1,
+            "updated_date_time": "2023-11-01T00:00:00Z",
+            "near_earth_objects": [{
+                "id": "3542519",
+                "neo_reference_id": "3542519",
+                "name": "(2010 PK9)",
+                "nasa_jpl_url": "http://ssd.jpl.nasa.gov/sbdb.cgi?sstr=3542519",
+                "absolute_magnitude_h": 21.0,
+                "estimated_diameter": {
+                    "kilometers": values, "meters": values, "miles": values, "feet": values
+                },
+                "is_potentially_hazardous_asteroid": hazardous,
+                "close_approach_data": [{
+                    "close_approach_date": "2023-11-02",
+                    "epoch_date_close_approach": epoch_millis,
+                    "relative_velocity": {
+                        "kilometers_per_second": "12.5",
+                        "kilometers_per_hour": "45000",
+                        "miles_per_hour": "27961"
+                    },
+                    "miss_distance": {
+                        "astronomical": "0.0123",
+                        "lunar": lunar.to_string(),
+                        "kilometers": "1840000",
+                        "miles": "1143000"
+                    },
+                    "orbiting_body": "Earth"
+                }],
+                "is_sentry_object": false,
+                "links": { "next": null, "prev": null, "this": null }
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn launches(t0: &str) -> UpcomingLaunches {
+        serde_json::from_value(serde_json::json!({
+            "valid_auth": true,
+            "count": 1,
+            "limit": 25,
+            "total": 1,
+            "last_page": 1,
+            "result": [{
+                "id": 4451,
+                "cospar_id": null,
+                "sort_date": "1698900000",
+                "name": "Starlink 6-26",
+                "provider": { "id": 1, "name": "SpaceX", "slug": "spacex" },
+                "vehicle": { "id": 1, "name": "Falcon 9", "company_id": 1, "slug": "falcon-9", "pad": null },
+                "pad": null,
+                "missions": [],
+                "mission_description": "",
+                "launch_description": "",
+                "win_open": t0,
+                "t0": t0,
+                "win_close": null,
+                "date_str": "Nov 02",
+                "tags": [],
+                "slug": "starlink-6-26",
+                "weather_summary": null,
+                "weather_temp": 0,
+                "weather_condition": null,
+                "weather_wind_mph": null,
+                "weather_icon": null,
+                "weather_updated": null,
+                "quick_text": null,
+                "suborbital": false,
+                "modified": "2023-11-01T00:00:00+00:00"
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn only_close_future_hazardous_approaches_are_notified() {
+        let tomorrow = (now() + Duration::days(1)).timestamp_millis();
+        let yesterday = (now() - Duration::days(1)).timestamp_millis();
+
+        let notifications = hazardous_approaches(&neos(true, tomorrow, 4.2), 10.0, now());
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].id, "neo:3542519:2023-11-02");
+        assert_eq!(notifications[0].data["miss_distance_lunar"], 4.2);
+
+        assert!(hazardous_approaches(&neos(false, tomorrow, 4.2), 10.0, now()).is_empty());
+        assert!(hazardous_approaches(&neos(true, yesterday, 4.2), 10.0, now()).is_empty());
+        assert!(hazardous_approaches(&neos(true, tomorrow, 42.0), 10.0, now()).is_empty());
+    }
+
+    #[test]
+    fn launches_are_announced_a_day_and_an_hour_out() {
+        let stage = |t0: &str| {
+            imminent_launches(&launches(t0), now())
+                .first()
+                .map(|notification| notification.data["stage"].as_str().unwrap().to_string())
+        };
+
+        assert_eq!(stage("2023-11-02T08:00:00Z"), Some("t-24h".to_string()));
+        assert_eq!(stage("2023-11-01T12:30:00Z"), Some("t-1h".to_string()));
+        assert_eq!(stage("2023-11-03T08:00:00Z"), None);
+        assert_eq!(stage("2023-11-01T11:00:00Z"), None);
+    }
+
+    #[test]
+    fn crew_changes_are_grouped_per_run() {
+        let log: PeopleEventLog = serde_json::from_value(serde_json::json!({
+            "events": [
+                { "name": "Old Timer", "craft": "ISS", "kind": "arrived", "timestamp": "2023-10-01 12:00:00 +0000" },
+                { "name": "Jasmin Moghbeli", "craft": "ISS", "kind": "arrived", "timestamp": "2023-11-01 08:00:00 +0000" },
+                { "name": "Stephen Bowen", "craft": "ISS", "kind": "departed", "timestamp": "2023-11-01 08:00:00 +0000" }
+            ]
+        }))
+        .unwrap();
+
+        let notifications = crew_changes(&log, now());
+
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].id, "crew:2023-11-01 08:00:00 +0000");
+        assert_eq!(
+            notifications[0].data["arrived"][0]["name"],
+            "Jasmin Moghbeli"
+        );
+        assert_eq!(
+            notifications[0].data["departed"][0]["name"],
+            "Stephen Bowen"
+        );
+    }
+
+    #[tokio::test]
+    async fn handler_delivers_signed_notifications() {
+        let server = StubServer::builder()
+            .respond("/hook", StubResponse::status(204))
+            .start()
+            .await;
+        let config = Config::default();
+        let store = MemoryStore::new();
+        store
+            .put(
+                &config.subscriptions_file,
+                &SubscriptionList {
+                    subscriptions: vec![Subscription {
+                        id: "ops".to_string(),
+                        url: server.url("/hook"),
+                        secret: "secret".to_string(),
+                        kinds: vec![NotificationKind::LaunchImminent],
+                        max_lunar_distance: None,
+                    }],
+                },
+            )
+            .await
+            .unwrap();
+        let t0 = rfc3339(Utc::now() + Duration::hours(3));
+        store
+            .put(&config.launches_file, &launches(&t0))
+            .await
+            .unwrap();
+
+        function_handler(&store, &config, Request::default())
+            .await
+            .unwrap();
+        function_handler(&store, &config, Request::default())
+            .await
+            .unwrap();
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert!(received[0]
+            .header(SIGNATURE_HEADER)
+            .is_some_and(|signature| signature.starts_with("sha256=")));
+        let notification: Notification = serde_json::from_str(&received[0].body).unwrap();
+        assert_eq!(notification.data["stage"], "t-24h");
+        assert_eq!(notification.data["net"], t0);
+    }
+
+    #[tokio::test]
+    async fn handler_does_nothing_without_subscriptions() {
+        let store = MemoryStore::new();
+
+        function_handler(&store, &Config::default(), Request::default())
+            .await
+            .unwrap();
+
+        assert!(store
+            .get::<shared::notify::DeliveryLedger>(&Config::default().notify_ledger_file)
+            .await
+            .is_err());
+    }
+}
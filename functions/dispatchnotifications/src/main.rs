@@ -0,0 +1,30 @@
+use dispatchnotifications::function_handler;
+use lambda_http::{run, service_fn, Error};
+use log::LevelFilter;
+use shared::config::Config;
+use shared::storage;
+use simple_logger::SimpleLogger;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    SimpleLogger::new()
+        .with_level(LevelFilter::Info)
+        .init()
+        .unwrap();
+
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .without_time()
+        .init();
+
+    let config = Config::from_env()?;
+    let store = storage::from_config(&config).await;
+    let store = store.as_ref();
+    let config = &config;
+
+    run(service_fn(move |event| {
+        function_handler(store, config, event)
+    }))
+    .await
+}
@@ -0,0 +1,248 @@
+use chrono::prelude::*;
+use lambda_http::{Error, Request, Response};
+use serde::{Deserialize, Serialize};
+use shared::apimodels::{PeopleInSpaceModel, PersonModel};
+use shared::config::Config;
+use shared::fetch::Fetcher;
+use shared::history::{diff_people, PeopleEvent, PeopleEventLog, TIMESTAMP_FORMAT};
+use shared::storage::{DocumentStore, DocumentStoreExt, StorageError};
+use shared::validation::{quarantine_key, replace_snapshot};
+
+#[derive(Deserialize, Serialize)]
+struct PeopleApiResponse {
+    message: String,
+    people: Vec<PersonModel>,
+}
+
+async fn retrieve_data_from_api(
+    fetcher: &Fetcher,
+    astros_url: &str,
+) -> Result<PeopleApiResponse, Error> {
+    log::info!("Retrieving data from API");
+    let model: PeopleApiResponse = fetcher.get_json(astros_url).await?;
+    log::info!("Successfully retrieved data from API");
+    Ok(model)
+}
+
+/// Arrivals and departures since the stored snapshot
+///
+/// A stored snapshot that no longer parses is quarantined and no events are
+/// recorded this run, rather than logging everyone aboard as a new arrival.
+async fn events_since_previous(
+    store: &dyn DocumentStore,
+    config: &Config,
+    current: &PeopleInSpaceModel,
+) -> Result<Vec<PeopleEvent>, Error> {
+    let previous = match store.get(&config.people_file).await {
+        Ok(previous) => Some(previous),
+        Err(StorageError::NotFound(_)) => None,
+        Err(StorageError::Serialization(err)) => {
+            let key = quarantine_key(&config.people_file, Utc::now().timestamp() as u64);
+            let corrupt = store.get_bytes(&config.people_file).await?;
+            store.put_bytes(&key, corrupt).await?;
+            log::warn!(
+                "Stored {} is unreadable ({}), moved it to {} and recorded no events",
+                config.people_file,
+                err,
+                key
+            );
+            return Ok(Vec::new());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(diff_people(previous.as_ref(), current))
+}
+
+/// Append arrival and departure events to the event log
+async fn record_events(
+    store: &dyn DocumentStore,
+    config: &Config,
+    events: Vec<PeopleEvent>,
+) -> Result<(), Error> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut log: PeopleEventLog = match store.get(&config.people_events_file).await {
+        Ok(log) => log,
+        Err(StorageError::NotFound(_)) => PeopleEventLog::default(),
+        Err(err) => return Err(err.into()),
+    };
+    log::info!("Recording {} arrival/departure events", events.len());
+    log.events.extend(events);
+    store.put(&config.people_events_file, &log).await?;
+
+    Ok(())
+}
+
+fn convert_to_model(api_response: PeopleApiResponse) -> PeopleInSpaceModel {
+    PeopleInSpaceModel {
+        update_time: Utc::now().format(TIMESTAMP_FORMAT).to_string(),
+        people: api_response.people,
+    }
+}
+
+pub async fn function_handler(
+    store: &dyn DocumentStore,
+    config: &Config,
+    _event: Request,
+) -> Result<Response<String>, Error> {
+    log::info!("Starting function handler");
+    let fetcher = Fetcher::new(config.retry_policy());
+    let api_response = retrieve_data_from_api(&fetcher, &config.astros_url).await?;
+    log::info!("Retrieved data from API");
+    let data = convert_to_model(api_response);
+    let events = events_since_previous(store, config, &data).await?;
+    log::info!("Writing data to store");
+    replace_snapshot(
+        store,
+        &config.people_file,
+        &data,
+        &config.snapshot_policy(config.people_max_change_ratio),
+    )
+    .await?;
+    record_events(store, config, events).await?;
+
+    Ok(Response::new("".to_string()))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::fetch::RetryPolicy;
+    use shared::history::PeopleEventKind;
+    use shared::storage::MemoryStore;
+    use shared::testing::{StubResponse, StubServer};
+    use shared::validation::QUARANTINE_PREFIX;
+    use std::time::Duration;
+
+    const ASTROS_FIXTURE: &str = include_str!("../fixtures/astros.json");
+
+    // Test retrieve_data_from_api
+    #[tokio::test]
+    async fn api_response_has_value() {
+        let server = StubServer::builder()
+            .respond("/astros.json", StubResponse::json(ASTROS_FIXTURE))
+            .start()
+            .await;
+
+        let value = retrieve_data_from_api(&Fetcher::default(), &server.url("/astros.json"))
+            .await
+            .unwrap();
+        assert_eq!(value.people.len(), 10);
+    }
+
+    #[tokio::test]
+    async fn flaky_api_is_retried() {
+        let server = StubServer::builder()
+            .respond("/astros.json", StubResponse::status(502))
+            .respond("/astros.json", StubResponse::json(ASTROS_FIXTURE))
+            .start()
+            .await;
+        let fetcher = Fetcher::new(RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        });
+
+        let value = retrieve_data_from_api(&fetcher, &server.url("/astros.json")).await;
+        assert!(value.is_ok());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn can_convert_api_response_to_model() {
+        let api_response: PeopleApiResponse = serde_json::from_str(ASTROS_FIXTURE).unwrap();
+
+        let model = convert_to_model(api_response);
+        assert_eq!(model.people.len(), 10);
+        assert_eq!(model.people[0].name, "Jasmin Moghbeli");
+    }
+
+    #[tokio::test]
+    async fn handler_logs_arrivals_and_departures() {
+        let server = StubServer::builder()
+            .respond("/astros.json", StubResponse::json(ASTROS_FIXTURE))
+            .start()
+            .await;
+        let config = Config {
+            astros_url: server.url("/astros.json"),
+            ..Config::default()
+        };
+
+        let api_response: PeopleApiResponse = serde_json::from_str(ASTROS_FIXTURE).unwrap();
+        let mut previous = convert_to_model(api_response);
+        previous.people.remove(0);
+        previous.people.push(PersonModel {
+            name: "Sergey Prokopyev".to_string(),
+            craft: "ISS".to_string(),
+        });
+        let store = MemoryStore::new();
+        store.put(&config.people_file, &previous).await.unwrap();
+
+        function_handler(&store, &config, Request::default())
+            .await
+            .unwrap();
+
+        let log: PeopleEventLog = store.get(&config.people_events_file).await.unwrap();
+        let events: Vec<(&str, PeopleEventKind)> = log
+            .events
+            .iter()
+            .map(|event| (event.name.as_str(), event.kind))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("Sergey Prokopyev", PeopleEventKind::Departed),
+                ("Jasmin Moghbeli", PeopleEventKind::Arrived),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn corrupt_snapshot_is_quarantined_instead_of_diffed() {
+        let server = StubServer::builder()
+            .respond("/astros.json", StubResponse::json(ASTROS_FIXTURE))
+            .start()
+            .await;
+        let config = Config {
+            astros_url: server.url("/astros.json"),
+            ..Config::default()
+        };
+        let store = MemoryStore::new();
+        store
+            .put_bytes(&config.people_file, b"{\"people\": [".to_vec())
+            .await
+            .unwrap();
+
+        function_handler(&store, &config, Request::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            store
+                .get::<PeopleEventLog>(&config.people_events_file)
+                .await,
+            Err(StorageError::NotFound(_))
+        ));
+        assert_eq!(store.list(QUARANTINE_PREFIX).await.unwrap().len(), 1);
+        let stored: PeopleInSpaceModel = store.get(&config.people_file).await.unwrap();
+        assert_eq!(stored.people.len(), 10);
+    }
+
+    /// Refresh the recorded fixture from the live API: `cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn record_astros_fixture() {
+        let body = Fetcher::default()
+            .get_text(&Config::default().astros_url)
+            .await
+            .unwrap();
+        std::fs::write(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/astros.json"),
+            body,
+        )
+        .unwrap();
+    }
+}
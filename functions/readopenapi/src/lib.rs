@@ -0,0 +1,49 @@
+use lambda_http::{Body, Error, Request, Response};
+use shared::caching::{cached_response, CachePolicy};
+use std::sync::OnceLock;
+
+/// The document only changes with a deploy
+const CACHE_POLICY: CachePolicy = CachePolicy {
+    max_age: 60 * 60,
+    vary: "Accept-Encoding",
+};
+
+/// The OpenAPI document, assembled once per function instance
+fn document() -> &'static str {
+    static DOCUMENT: OnceLock<String> = OnceLock::new();
+    DOCUMENT.get_or_init(|| shared::openapi::document().to_string())
+}
+
+/// `/openapi.json`, describing the people, NEO and launch endpoints
+pub async fn function_handler(event: Request) -> Result<Response<Body>, Error> {
+    cached_response(
+        &event,
+        "application/json",
+        document().to_string(),
+        None,
+        CACHE_POLICY,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_the_openapi_document() {
+        let response = function_handler(Request::default()).await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["openapi"], "3.1.0");
+        assert!(body["paths"]["/launches"]["get"].is_object());
+
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert("if-none-match", response.headers()["ETag"].clone());
+        let response = function_handler(request).await.unwrap();
+        assert_eq!(response.status(), 304);
+    }
+}
@@ -0,0 +1,409 @@
+use lambda_http::{Body, Error, Request, RequestExt, Response};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use shared::apimodels::{CraftGroup, EnrichedPerson, PeopleByCraftResponse, PeopleInSpaceModel};
+use shared::caching::{cached_response, response_builder, CachePolicy};
+use shared::config::Config;
+use shared::craft::Craft;
+use shared::error::{ApiError, PROBLEM_JSON};
+use shared::history::{parse_timestamp, PeopleEventLog};
+use shared::storage::{DocumentStore, DocumentStoreExt, StorageError};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The collector runs every two hours; clients may reuse a response for a
+/// fraction of that so a new snapshot shows up soon after it is written
+const CACHE_POLICY: CachePolicy = CachePolicy {
+    max_age: 15 * 60,
+    vary: "Accept-Encoding",
+};
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Person {
+    name: String,
+    craft: Craft,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct PeopleInSpaceResponse {
+    update_time: String,
+    people: Vec<Person>,
+}
+
+/// Group people by craft, keeping the order crafts first appear in
+fn group_by_craft(people: &[Person]) -> Vec<CraftGroup<'_>> {
+    let mut groups: Vec<CraftGroup> = Vec::new();
+    for person in people {
+        match groups
+            .iter_mut()
+            .find(|group| group.name == person.craft.name())
+        {
+            Some(group) => group.people.push(&person.name),
+            None => groups.push(CraftGroup {
+                name: person.craft.name(),
+                info: person.craft.info(),
+                people: vec![&person.name],
+            }),
+        }
+    }
+    groups
+}
+
+/// Resource of the people API a request was made against
+#[derive(Debug, PartialEq)]
+enum PeopleRoute {
+    /// `/people`
+    Current,
+    /// `/people/history`
+    History,
+    /// `/people/{name}`
+    Person(String),
+}
+
+fn people_route(event: &Request) -> PeopleRoute {
+    if let Some(name) = event
+        .path_parameters_ref()
+        .and_then(|params| params.first("name"))
+    {
+        let name = percent_decode_str(name).decode_utf8_lossy();
+        return PeopleRoute::Person(name.into_owned());
+    }
+
+    if event
+        .uri()
+        .path()
+        .trim_end_matches('/')
+        .ends_with("/history")
+    {
+        PeopleRoute::History
+    } else {
+        PeopleRoute::Current
+    }
+}
+
+/// A response body and when the newest document it was built from was written
+type Rendered = (String, Option<SystemTime>);
+
+/// The event log, empty until the collector has recorded its first snapshot
+async fn event_log(
+    store: &dyn DocumentStore,
+    config: &Config,
+) -> Result<(Arc<PeopleEventLog>, Option<SystemTime>), StorageError> {
+    match store.get_cached(&config.people_events_file).await {
+        Err(StorageError::NotFound(_)) => Ok((Arc::default(), None)),
+        result => result,
+    }
+}
+
+async fn current_people(store: &dyn DocumentStore, config: &Config) -> Result<Rendered, ApiError> {
+    let (temp, people_modified) = store
+        .get_cached::<PeopleInSpaceResponse>(&config.people_file)
+        .await?;
+
+    log::info!("Updated date for found values: {}", temp.update_time);
+
+    // Durations run up to the snapshot time, not the time of the request
+    let (log, log_modified) = event_log(store, config).await?;
+    let now = parse_timestamp(&temp.update_time);
+    let people = temp
+        .people
+        .iter()
+        .map(|person| {
+            let days = now.map(|now| log.days_in_orbit(&person.name, now));
+            EnrichedPerson {
+                name: &person.name,
+                craft: &person.craft,
+                days_in_orbit_current_stint: days.and_then(|(current, _)| current),
+                tracked_days: days.map(|(_, total)| total),
+            }
+        })
+        .collect();
+
+    let body = PeopleByCraftResponse {
+        update_time: &temp.update_time,
+        people,
+        crafts: group_by_craft(&temp.people),
+        stats: now.map(|now| log.stats(now)),
+    };
+    Ok((
+        serde_json::to_string(&body).unwrap_or_default(),
+        people_modified.max(log_modified),
+    ))
+}
+
+async fn person_history(
+    store: &dyn DocumentStore,
+    config: &Config,
+    name: &str,
+) -> Result<Rendered, ApiError> {
+    let (current, people_modified): (Option<Arc<PeopleInSpaceModel>>, _) =
+        match store.get_cached(&config.people_file).await {
+            Ok((current, modified)) => (Some(current), modified),
+            Err(StorageError::NotFound(_)) => (None, None),
+            Err(err) => return Err(err.into()),
+        };
+
+    let (log, log_modified) = event_log(store, config).await?;
+    let history = log
+        .person(name, current.as_deref())
+        .ok_or_else(|| ApiError::NotFound(format!("{} has not been seen in orbit", name)))?;
+    Ok((
+        serde_json::to_string(&history).unwrap_or_default(),
+        people_modified.max(log_modified),
+    ))
+}
+
+async fn respond(
+    store: &dyn DocumentStore,
+    config: &Config,
+    event: &Request,
+) -> Result<Rendered, ApiError> {
+    match people_route(event) {
+        PeopleRoute::Current => current_people(store, config).await,
+        PeopleRoute::History => {
+            let (log, modified) = event_log(store, config).await?;
+            Ok((serde_json::to_string(&*log).unwrap_or_default(), modified))
+        }
+        PeopleRoute::Person(name) => person_history(store, config, &name).await,
+    }
+}
+
+fn error_response(err: ApiError) -> Result<Response<Body>, Error> {
+    tracing::warn!("Request failed: {}", err);
+
+    let resp = response_builder(err.status(), PROBLEM_JSON)
+        .body(err.to_problem_json().into())
+        .map_err(Box::new)?;
+    Ok(resp)
+}
+
+pub async fn function_handler(
+    store: &dyn DocumentStore,
+    config: &Config,
+    event: Request,
+) -> Result<Response<Body>, Error> {
+    let (body, last_modified) = match respond(store, config, &event).await {
+        Ok(rendered) => rendered,
+        Err(err) => return error_response(err),
+    };
+
+    cached_response(
+        &event,
+        "application/json",
+        body,
+        last_modified,
+        CACHE_POLICY,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lambda_http::http;
+    use shared::storage::MemoryStore;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn handler_reads_people_from_store() {
+        let store = MemoryStore::new();
+        store
+            .put(
+                &Config::default().people_file,
+                &serde_json::json!({
+                    "update_time": "2023-11-01 00:00:00 +0000",
+                    "people": [{ "name": "Jasmin Moghbeli", "craft": "ISS" }]
+                }),
+            )
+            .await
+            .unwrap();
+
+        let response = function_handler(&store, &Config::default(), Request::default())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["people"][0]["name"], "Jasmin Moghbeli");
+    }
+
+    #[tokio::test]
+    async fn unchanged_people_are_not_modified() {
+        let store = history_store().await;
+        let response = function_handler(&store, &Config::default(), Request::default())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=900");
+        let etag = response.headers()["ETag"].clone();
+        let last_modified = response.headers()["Last-Modified"].clone();
+
+        let mut request = Request::default();
+        request.headers_mut().insert("if-none-match", etag.clone());
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.headers()["ETag"], etag);
+        assert!(response.body().is_empty());
+
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert("if-modified-since", last_modified);
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert("if-none-match", "\"stale\"".parse().unwrap());
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn missing_document_is_not_found() {
+        let store = MemoryStore::new();
+        let response = function_handler(&store, &Config::default(), Request::default())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.headers()["content-type"], PROBLEM_JSON);
+    }
+
+    #[tokio::test]
+    async fn people_are_grouped_by_craft() {
+        let store = MemoryStore::new();
+        store
+            .put(
+                &Config::default().people_file,
+                &serde_json::json!({
+                    "update_time": "2023-11-01 00:00:00 +0000",
+                    "people": [
+                        { "name": "Jasmin Moghbeli", "craft": "ISS" },
+                        { "name": "Tang Hongbo", "craft": "Tiangong" },
+                        { "name": "Loral O'Hara", "craft": "ISS" },
+                        { "name": "Peggy Whitson", "craft": "Axiom Mission 2" }
+                    ]
+                }),
+            )
+            .await
+            .unwrap();
+
+        let response = function_handler(&store, &Config::default(), Request::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let crafts = body["crafts"].as_array().unwrap();
+        assert_eq!(crafts.len(), 3);
+        assert_eq!(crafts[0]["name"], "ISS");
+        assert_eq!(crafts[0]["kind"], "station");
+        assert_eq!(
+            crafts[0]["people"],
+            serde_json::json!(["Jasmin Moghbeli", "Loral O'Hara"])
+        );
+        assert_eq!(crafts[1]["operator"], "CMSA");
+        assert_eq!(crafts[2]["name"], "Axiom Mission 2");
+        assert!(crafts[2].get("operator").is_none());
+        assert_eq!(body["people"][3]["craft"], "Axiom Mission 2");
+    }
+
+    async fn history_store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store
+            .put(
+                &Config::default().people_file,
+                &serde_json::json!({
+                    "update_time": "2023-11-02 00:00:00 +0000",
+                    "people": [{ "name": "Loral O'Hara", "craft": "ISS" }]
+                }),
+            )
+            .await
+            .unwrap();
+        store
+            .put(
+                &Config::default().people_events_file,
+                &serde_json::json!({ "events": [
+                    { "name": "Andreas Mogensen", "craft": "ISS", "kind": "arrived", "timestamp": "2023-08-27 00:00:00 +0000" },
+                    { "name": "Loral O'Hara", "craft": "ISS", "kind": "arrived", "timestamp": "2023-09-15 00:00:00 +0000" },
+                    { "name": "Andreas Mogensen", "craft": "ISS", "kind": "departed", "timestamp": "2023-11-02 00:00:00 +0000" }
+                ]}),
+            )
+            .await
+            .unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn people_are_enriched_with_time_in_orbit() {
+        let store = history_store().await;
+
+        let response = function_handler(&store, &Config::default(), Request::default())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["people"][0]["name"], "Loral O'Hara");
+        assert_eq!(body["people"][0]["craft"], "ISS");
+        assert_eq!(body["people"][0]["days_in_orbit_current_stint"], 48);
+        assert_eq!(body["people"][0]["tracked_days"], 48);
+        assert_eq!(body["stats"]["person_days_this_year"], 48 + 67);
+        assert_eq!(body["stats"]["crew_timeline"][2]["crews"]["ISS"], 1);
+    }
+
+    #[tokio::test]
+    async fn history_lists_every_event() {
+        let store = history_store().await;
+        let request = http::Request::get("/people/history")
+            .body(Body::Empty)
+            .unwrap();
+
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["events"].as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn person_reports_first_and_last_seen() {
+        let store = history_store().await;
+        let request = Request::default().with_path_parameters(HashMap::from([(
+            "name".to_string(),
+            "andreas%20mogensen".to_string(),
+        )]));
+
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["name"], "Andreas Mogensen");
+        assert_eq!(body["in_orbit"], false);
+        assert_eq!(body["first_seen"], "2023-08-27 00:00:00 +0000");
+        assert_eq!(body["last_seen"], "2023-11-02 00:00:00 +0000");
+    }
+
+    #[tokio::test]
+    async fn unknown_person_is_not_found() {
+        let store = history_store().await;
+        let request = Request::default()
+            .with_path_parameters(HashMap::from([("name".to_string(), "Nobody".to_string())]));
+
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
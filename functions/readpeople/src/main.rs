@@ -1,64 +1,10 @@
-use aws_sdk_s3 as s3;
-use lambda_http::{run, service_fn, Body, Error, Request, Response};
+use lambda_http::{run, service_fn, Error};
 use log::LevelFilter;
-use serde::{Deserialize, Serialize};
+use readpeople::function_handler;
+use shared::config::Config;
+use shared::storage;
 use simple_logger::SimpleLogger;
 
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct Person {
-    name: String,
-    craft: Craft,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct PeopleInSpaceResponse {
-    update_time: String,
-    people: Vec<Person>,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-enum Craft {
-    #[serde(alias = "ISS")]
-    ISS,
-    #[serde(alias = "Shenzhou 15")]
-    Shenzhou15,
-    #[serde(alias = "Tiangong")]
-    Tiangong,
-}
-
-async fn function_handler(_event: Request) -> Result<Response<Body>, Error> {
-    let config = aws_config::load_from_env().await;
-    let client = s3::Client::new(&config);
-    let data = client
-        .get_object()
-        .bucket("spaceclouddatabucket")
-        .key("people_in_space.json")
-        .send()
-        .await?;
-
-    let bytes = data.body.collect().await?.into_bytes();
-    let response = std::str::from_utf8(&bytes)?;
-    let temp: PeopleInSpaceResponse = serde_json::from_str(response).unwrap();
-
-    log::info!("Updated date for found values: {}", temp.update_time);
-
-    let resp = Response::builder()
-        .status(200)
-        .header("content-type", "application/json")
-        .header("Access-Control-Allow-Origin", "*")
-        .header(
-            "Access-Control-Allow-Headers",
-            "Content-Type,Authorization,",
-        )
-        .header("Access-Control-Allow-Methods", "GET")
-        // TODO look into the unwrap bit
-        .body(serde_json::to_string(&temp).unwrap_or_default().into())
-        .map_err(Box::new)?;
-
-    return Ok(resp);
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     SimpleLogger::new()
@@ -72,5 +18,13 @@ async fn main() -> Result<(), Error> {
         .without_time()
         .init();
 
-    run(service_fn(function_handler)).await
+    let config = Config::from_env()?;
+    let store = storage::from_config(&config).await;
+    let store = store.as_ref();
+    let config = &config;
+
+    run(service_fn(move |event| {
+        function_handler(store, config, event)
+    }))
+    .await
 }
@@ -0,0 +1,160 @@
+//! iCalendar (RFC 5545) rendering of upcoming launches
+use chrono::{DateTime, Utc};
+use shared::history::{LaunchChangeKind, LaunchChangeLog};
+use shared::persistencemodels::UpcomingLaunchesLaunch;
+use std::collections::HashMap;
+
+pub const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Content lines longer than this many octets are folded
+const MAX_LINE_OCTETS: usize = 75;
+
+fn utc(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Append `name:value` to `out`, folding it onto continuation lines without
+/// splitting a UTF-8 character
+fn push_line(out: &mut String, name: &str, value: &str) {
+    let line = format!("{}:{}", name, value);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(c);
+        octets += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn location(launch: &UpcomingLaunchesLaunch) -> Option<String> {
+    let pad = launch.pad.as_ref()?;
+    let parts = [
+        pad.name.as_str(),
+        pad.location.name.as_str(),
+        pad.location.state_name.as_str(),
+        pad.location.country.as_str(),
+    ];
+    let parts: Vec<&str> = parts.into_iter().filter(|part| !part.is_empty()).collect();
+    Some(parts.join(", "))
+}
+
+/// SEQUENCE of each launch that was revised: the published version is
+/// revision 0 and every recorded change to a listed launch adds one, so it
+/// moves with the launch's `modified` time
+fn sequences(changes: &LaunchChangeLog) -> HashMap<u64, usize> {
+    let mut sequences = HashMap::new();
+    for change in &changes.changes {
+        if !matches!(
+            change.kind,
+            LaunchChangeKind::Added | LaunchChangeKind::Removed
+        ) {
+            *sequences.entry(change.id).or_default() += 1;
+        }
+    }
+    sequences
+}
+
+/// One VEVENT, or `None` while the launch has no T-0 or window to place it at
+fn push_event(out: &mut String, launch: &UpcomingLaunchesLaunch, sequence: usize) -> Option<()> {
+    let start = launch
+        .t0
+        .as_deref()
+        .or(launch.win_open.as_deref())
+        .and_then(utc)?;
+    let end = launch
+        .win_close
+        .as_deref()
+        .and_then(utc)
+        .filter(|end| *end > start);
+    let modified = utc(&launch.modified);
+
+    push_line(out, "BEGIN", "VEVENT");
+    push_line(out, "UID", &format!("launch-{}@spacebits", launch.id));
+    push_line(out, "DTSTAMP", &format_time(modified.unwrap_or(start)));
+    push_line(out, "SEQUENCE", &sequence.to_string());
+    push_line(out, "DTSTART", &format_time(start));
+    if let Some(end) = end {
+        push_line(out, "DTEND", &format_time(end));
+    }
+    push_line(out, "SUMMARY", &escape(&launch.name));
+    if let Some(location) = location(launch) {
+        push_line(out, "LOCATION", &escape(&location));
+    }
+    if !launch.launch_description.is_empty() {
+        push_line(out, "DESCRIPTION", &escape(&launch.launch_description));
+    }
+    push_line(
+        out,
+        "URL",
+        &format!("https://www.rocketlaunch.live/launch/{}", launch.slug),
+    );
+    push_line(out, "END", "VEVENT");
+
+    Some(())
+}
+
+/// The launches as a calendar, numbering each event's revisions from the
+/// changes recorded in `changes`
+pub fn calendar<'a>(
+    launches: impl IntoIterator<Item = &'a UpcomingLaunchesLaunch>,
+    changes: &LaunchChangeLog,
+) -> String {
+    let sequences = sequences(changes);
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN", "VCALENDAR");
+    push_line(&mut out, "VERSION", "2.0");
+    push_line(&mut out, "PRODID", "-//SpaceBits//Upcoming Launches//EN");
+    push_line(&mut out, "CALSCALE", "GREGORIAN");
+    push_line(&mut out, "METHOD", "PUBLISH");
+    push_line(&mut out, "X-WR-CALNAME", "Upcoming Launches");
+    for launch in launches {
+        let sequence = sequences.get(&launch.id).copied().unwrap_or_default();
+        if push_event(&mut out, launch, sequence).is_none() {
+            log::info!(
+                "Leaving launch {} out of the calendar, it has no time yet",
+                launch.id
+            );
+        }
+    }
+    push_line(&mut out, "END", "VCALENDAR");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape("a, b; c\\d\ne"), "a\\, b\\; c\\\\d\\ne");
+    }
+
+    #[test]
+    fn long_lines_are_folded_on_character_boundaries() {
+        let mut out = String::new();
+        push_line(&mut out, "SUMMARY", &"é".repeat(60));
+
+        let lines: Vec<&str> = out.trim_end().split("\r\n").collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+    }
+}
@@ -0,0 +1,815 @@
+mod ics;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use lambda_http::{Body, Error, Request, RequestExt, Response};
+use shared::caching::{cached_response, rendered_response, response_builder, CachePolicy};
+use shared::config::Config;
+use shared::error::{ApiError, PROBLEM_JSON};
+use shared::export::{launch_rows, to_csv, to_ndjson, Format};
+use shared::history::{LaunchChangeLog, LaunchHistoryResponse};
+use shared::persistencemodels::{
+    UpcomingLaunches, UpcomingLaunchesLaunch, UpcomingLaunchesResponse,
+};
+use shared::storage::{DocumentStore, DocumentStoreExt, StorageError};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The collector runs every two hours; clients may reuse a response for a
+/// fraction of that so a slipped launch shows up soon after it is collected
+const CACHE_POLICY: CachePolicy = CachePolicy {
+    max_age: 15 * 60,
+    vary: "Accept, Accept-Encoding",
+};
+
+/// Position of a launch in the listing, ordered by `sort_date` then `id`
+///
+/// Encoded as `{sort_date}-{id}` so a cursor stays valid when the launch it
+/// points at is dropped by the next collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Cursor {
+    sort_date: u64,
+    id: u64,
+}
+
+impl Cursor {
+    fn of(launch: &UpcomingLaunchesLaunch) -> Self {
+        let (sort_date, id) = launch.listing_order();
+        Cursor { sort_date, id }
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (sort_date, id) = value.split_once('-').ok_or(())?;
+        Ok(Cursor {
+            sort_date: sort_date.parse().map_err(|_| ())?,
+            id: id.parse().map_err(|_| ())?,
+        })
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.sort_date, self.id)
+    }
+}
+
+/// Filters, search and paging requested through the query string
+#[derive(Debug, Default, PartialEq)]
+struct LaunchQuery {
+    provider: Option<String>,
+    vehicle: Option<String>,
+    pad: Option<String>,
+    country: Option<String>,
+    state: Option<String>,
+    tag: Option<String>,
+    suborbital: Option<bool>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    search: Option<String>,
+    limit: Option<usize>,
+    cursor: Option<Cursor>,
+}
+
+fn query_param<'a, T: FromStr>(
+    params: &impl Fn(&str) -> Option<&'a str>,
+    name: &str,
+) -> Result<Option<T>, String> {
+    match params(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid value for {}: {}", name, value)),
+        None => Ok(None),
+    }
+}
+
+/// Parse a window bound; a bare day starts at midnight for `from` and runs
+/// to the end of the day for `to`
+fn bound_param<'a>(
+    params: &impl Fn(&str) -> Option<&'a str>,
+    name: &str,
+    end_of_day: bool,
+) -> Result<Option<DateTime<Utc>>, String> {
+    let Some(value) = query_param::<String>(params, name)? else {
+        return Ok(None);
+    };
+
+    if let Ok(instant) = DateTime::parse_from_rfc3339(&value) {
+        return Ok(Some(instant.with_timezone(&Utc)));
+    }
+    NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+        .ok()
+        .and_then(|day| {
+            if end_of_day {
+                day.and_hms_opt(23, 59, 59)
+            } else {
+                day.and_hms_opt(0, 0, 0)
+            }
+        })
+        .map(|midnight| Some(midnight.and_utc()))
+        .ok_or_else(|| {
+            format!(
+                "{} must be an RFC 3339 time or YYYY-MM-DD, got {}",
+                name, value
+            )
+        })
+}
+
+/// Launch time from `t0`, or the start of the window when there is no T-0 yet
+fn launch_time(launch: &UpcomingLaunchesLaunch) -> Option<DateTime<Utc>> {
+    launch
+        .t0
+        .as_deref()
+        .or(launch.win_open.as_deref())
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(needle)
+}
+
+impl LaunchQuery {
+    fn from_request(event: &Request) -> Result<Self, String> {
+        let params = event.query_string_parameters_ref();
+        Self::from_params(&|name| params.and_then(|params| params.first(name)))
+    }
+
+    /// Parse the query from `params`, which looks a parameter up by name
+    fn from_params<'a>(params: &impl Fn(&str) -> Option<&'a str>) -> Result<Self, String> {
+        let query = LaunchQuery {
+            provider: query_param(params, "provider")?,
+            vehicle: query_param(params, "vehicle")?,
+            pad: query_param(params, "pad")?,
+            country: query_param(params, "country")?,
+            state: query_param(params, "state")?,
+            tag: query_param(params, "tag")?,
+            suborbital: query_param(params, "suborbital")?,
+            from: bound_param(params, "from", false)?,
+            to: bound_param(params, "to", true)?,
+            search: query_param::<String>(params, "q")?.map(|search| search.to_lowercase()),
+            limit: query_param(params, "limit")?,
+            cursor: query_param(params, "cursor")?,
+        };
+
+        if query.limit == Some(0) {
+            return Err("limit must be at least 1".to_string());
+        }
+        Ok(query)
+    }
+
+    fn matches(&self, launch: &UpcomingLaunchesLaunch) -> bool {
+        let location = launch.pad.as_ref().map(|pad| &pad.location);
+        let time = launch_time(launch);
+
+        self.provider
+            .as_ref()
+            .is_none_or(|slug| launch.provider.slug.eq_ignore_ascii_case(slug))
+            && self
+                .vehicle
+                .as_ref()
+                .is_none_or(|slug| launch.vehicle.slug.eq_ignore_ascii_case(slug))
+            && self.pad.as_ref().is_none_or(|pad| {
+                launch.pad.as_ref().is_some_and(|launch_pad| {
+                    launch_pad.name.eq_ignore_ascii_case(pad)
+                        || launch_pad.location.slug.eq_ignore_ascii_case(pad)
+                })
+            })
+            && self.country.as_ref().is_none_or(|country| {
+                location.is_some_and(|location| location.country.eq_ignore_ascii_case(country))
+            })
+            && self.state.as_ref().is_none_or(|state| {
+                location.is_some_and(|location| {
+                    location.state.eq_ignore_ascii_case(state)
+                        || location.state_name.eq_ignore_ascii_case(state)
+                })
+            })
+            && self.tag.as_ref().is_none_or(|tag| {
+                launch
+                    .tags
+                    .iter()
+                    .any(|launch_tag| launch_tag.text.eq_ignore_ascii_case(tag))
+            })
+            && self
+                .suborbital
+                .is_none_or(|suborbital| launch.suborbital == suborbital)
+            && self
+                .from
+                .is_none_or(|from| time.is_some_and(|time| time >= from))
+            && self.to.is_none_or(|to| time.is_some_and(|time| time <= to))
+            && self.search.as_ref().is_none_or(|search| {
+                contains_ignore_case(&launch.name, search)
+                    || contains_ignore_case(&launch.mission_description, search)
+                    || launch
+                        .missions
+                        .iter()
+                        .any(|mission| contains_ignore_case(&mission.name, search))
+            })
+    }
+
+    /// Filter the launches, returning the number that matched, the requested
+    /// page and the cursor of the page after it
+    fn apply(
+        &self,
+        launches: &[UpcomingLaunchesLaunch],
+    ) -> (usize, Vec<UpcomingLaunchesLaunch>, Option<Cursor>) {
+        let mut launches: Vec<_> = launches
+            .iter()
+            .filter(|launch| self.matches(launch))
+            .collect();
+        launches.sort_by_key(|launch| Cursor::of(launch));
+
+        let filtered_count = launches.len();
+        let mut page: Vec<_> = launches
+            .into_iter()
+            .filter(|launch| self.cursor.is_none_or(|cursor| Cursor::of(launch) > cursor))
+            .take(
+                self.limit
+                    .map_or(usize::MAX, |limit| limit.saturating_add(1)),
+            )
+            .cloned()
+            .collect();
+
+        let next_cursor = match self.limit {
+            Some(limit) if page.len() > limit => {
+                page.truncate(limit);
+                page.last().map(Cursor::of)
+            }
+            _ => None,
+        };
+
+        (filtered_count, page, next_cursor)
+    }
+}
+
+/// Launch named by the `{id}` path parameter of `/launches/{id}/history`
+fn requested_launch(event: &Request) -> Option<&str> {
+    event
+        .path_parameters_ref()
+        .and_then(|params| params.first("id"))
+}
+
+/// Every recorded schedule change, empty before the collector logged any
+async fn change_log(
+    store: &dyn DocumentStore,
+    config: &Config,
+) -> Result<(Arc<LaunchChangeLog>, Option<SystemTime>), ApiError> {
+    match store.get_cached(&config.launch_changes_file).await {
+        Ok(log) => Ok(log),
+        Err(StorageError::NotFound(_)) => Ok((Arc::default(), None)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Schedule changes recorded for one launch; a launch with no changes is
+/// only found if it is in the current listing
+async fn launch_history(
+    store: &dyn DocumentStore,
+    config: &Config,
+    id: &str,
+) -> Result<(LaunchHistoryResponse, Option<SystemTime>), ApiError> {
+    let id: u64 = id
+        .parse()
+        .map_err(|_| ApiError::BadRequest(format!("Launch id must be a number, got {}", id)))?;
+
+    let (log, mut modified) = change_log(store, config).await?;
+    let changes = log.for_launch(id);
+
+    if changes.is_empty() {
+        let (launches, launches_modified) =
+            retrieve_json_data(store, &config.launches_file).await?;
+        if !launches.result.iter().any(|launch| launch.id == id) {
+            return Err(ApiError::NotFound(format!("No launch with id {}", id)));
+        }
+        modified = modified.max(launches_modified);
+    }
+
+    Ok((LaunchHistoryResponse { id, changes }, modified))
+}
+
+/// Representation asked for through `?format=` or `Accept`
+fn requested_format(event: &Request) -> Result<Format, String> {
+    let format = event
+        .query_string_parameters_ref()
+        .and_then(|params| params.first("format"));
+    Format::from_request(event, format)
+}
+
+/// `/launches.ics` serves the filtered launches as a calendar feed, which
+/// ignores `limit` and `cursor`
+fn wants_calendar(event: &Request) -> bool {
+    event.uri().path().ends_with(".ics")
+}
+
+pub async fn function_handler(
+    store: &dyn DocumentStore,
+    config: &Config,
+    event: Request,
+) -> Result<Response<Body>, Error> {
+    if let Some(id) = requested_launch(&event) {
+        let (history, modified) = match launch_history(store, config, id).await {
+            Ok(history) => history,
+            Err(err) => return error_response(err),
+        };
+        let body = match serde_json::to_string(&history) {
+            Ok(body) => body,
+            Err(err) => {
+                return error_response(ApiError::CorruptDocument(format!(
+                    "Could not export the launch history: {}",
+                    err
+                )))
+            }
+        };
+        return cached_response(&event, "application/json", body, modified, CACHE_POLICY);
+    }
+
+    // Every launch in listing order is stored ready to send by the collector
+    let unfiltered = event.query_string_parameters_ref().is_none() && !wants_calendar(&event);
+    if unfiltered && requested_format(&event) == Ok(Format::Json) {
+        let content_type = Format::Json.content_type();
+        match rendered_response(
+            store,
+            &event,
+            &config.launches_file,
+            content_type,
+            CACHE_POLICY,
+        )
+        .await
+        {
+            Ok(Some(response)) => return Ok(response),
+            Ok(None) => {}
+            Err(err) => tracing::warn!("Could not read the rendered response: {}", err),
+        }
+    }
+
+    let query = match LaunchQuery::from_request(&event) {
+        Ok(query) => query,
+        Err(err) => return error_response(ApiError::BadRequest(err)),
+    };
+    let (launches, modified) = match retrieve_json_data(store, &config.launches_file).await {
+        Ok(launches) => launches,
+        Err(err) => return error_response(err),
+    };
+
+    if wants_calendar(&event) {
+        let (changes, changes_modified) = match change_log(store, config).await {
+            Ok(changes) => changes,
+            Err(err) => return error_response(err),
+        };
+        let launches = launches
+            .result
+            .iter()
+            .filter(|launch| query.matches(launch));
+        let body = ics::calendar(launches, &changes);
+        return cached_response(
+            &event,
+            ics::CALENDAR_CONTENT_TYPE,
+            body,
+            modified.max(changes_modified),
+            CACHE_POLICY,
+        );
+    }
+
+    let format = match requested_format(&event) {
+        Ok(format) => format,
+        Err(err) => return error_response(ApiError::BadRequest(err)),
+    };
+    let result = generate_response(&launches, &query);
+    let page = &result.launches.result;
+    let body = match format {
+        Format::Json => serde_json::to_string(&result).map_err(|err| err.to_string()),
+        Format::Csv => to_csv(&launch_rows(page)).map_err(|err| err.to_string()),
+        Format::Ndjson => to_ndjson(&launch_rows(page)).map_err(|err| err.to_string()),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(err) => {
+            return error_response(ApiError::CorruptDocument(format!(
+                "Could not export the stored data as {}: {}",
+                format.content_type(),
+                err
+            )))
+        }
+    };
+
+    cached_response(&event, format.content_type(), body, modified, CACHE_POLICY)
+}
+
+fn error_response(err: ApiError) -> Result<Response<Body>, Error> {
+    tracing::warn!("Request failed: {}", err);
+
+    let resp = response_builder(err.status(), PROBLEM_JSON)
+        .body(err.to_problem_json().into())
+        .map_err(Box::new)?;
+    Ok(resp)
+}
+
+/// Filter the stored launches and cut out the page after `query`'s cursor
+fn generate_response(stored: &UpcomingLaunches, query: &LaunchQuery) -> UpcomingLaunchesResponse {
+    let (filtered_count, page, next_cursor) = query.apply(&stored.result);
+    UpcomingLaunchesResponse::new(
+        stored,
+        filtered_count,
+        page,
+        next_cursor.map(|cursor| cursor.to_string()),
+    )
+}
+
+async fn retrieve_json_data(
+    store: &dyn DocumentStore,
+    file_name: &str,
+) -> Result<(Arc<UpcomingLaunches>, Option<SystemTime>), ApiError> {
+    let launches = store.get_cached(file_name).await?;
+
+    Ok(launches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lambda_http::http;
+    use shared::storage::{rendered_key, MemoryStore};
+    use std::cell::RefCell;
+    use std::collections::{BTreeSet, HashMap};
+
+    #[tokio::test]
+    async fn missing_document_is_problem_json() {
+        let response =
+            function_handler(&MemoryStore::new(), &Config::default(), Request::default())
+                .await
+                .unwrap();
+
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.headers()["content-type"], PROBLEM_JSON);
+        assert_eq!(response.headers()["Access-Control-Allow-Methods"], "GET");
+    }
+
+    fn launch(
+        id: u64,
+        sort_date: u64,
+        name: &str,
+        provider: &str,
+        country: &str,
+        t0: Option<&str>,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "cospar_id": null,
+            "sort_date": sort_date.to_string(),
+            "name": name,
+            "provider": { "id": 1, "name": provider, "slug": provider.to_lowercase() },
+            "vehicle": { "id": 1, "name": "Falcon 9", "company_id": 1, "slug": "falcon-9", "pad": null },
+            "pad": {
+                "id": 2,
+                "name": "LC-39A",
+                "location": {
+                    "id": 61,
+                    "name": "Kennedy Space Center",
+                    "state": "FL",
+                    "statename": "Florida",
+                    "country": country,
+                    "slug": "kennedy-space-center"
+                }
+            },
+            "missions": [{ "id": id, "name": format!("{} payload", name), "description": null }],
+            "mission_description": "",
+            "launch_description": "",
+            "win_open": t0,
+            "t0": t0,
+            "win_close": null,
+            "date_str": "Nov 03",
+            "tags": [{ "id": 1, "text": "Crewed" }],
+            "slug": name.to_lowercase(),
+            "weather_summary": null,
+            "weather_temp": 0,
+            "weather_condition": null,
+            "weather_wind_mph": null,
+            "weather_icon": null,
+            "weather_updated": null,
+            "quick_text": null,
+            "suborbital": false,
+            "modified": format!("2023-11-0{}T00:00:00+00:00", id)
+        })
+    }
+
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store
+            .put(
+                &Config::default().launches_file,
+                &serde_json::json!({
+                    "valid_auth": true,
+                    "count": 4,
+                    "limit": 4,
+                    "total": 4,
+                    "last_page": 1,
+                    "result": [
+                        launch(3, 1699300000, "Starlink 6-28", "SpaceX", "United States", Some("2023-11-06T20:00:00Z")),
+                        launch(1, 1699001100, "Starlink 6-27", "SpaceX", "United States", Some("2023-11-03T08:45:00Z")),
+                        launch(2, 1699100000, "Electron Test", "Rocket Lab", "New Zealand", Some("2023-11-04T12:00:00Z")),
+                        launch(4, 1699900000, "Vulcan Cert-1", "ULA", "United States", None),
+                    ]
+                }),
+            )
+            .await
+            .unwrap();
+        store
+    }
+
+    async fn query(params: &[(&str, &str)]) -> (u16, serde_json::Value) {
+        let request = Request::default().with_query_string_parameters(
+            params
+                .iter()
+                .map(|(name, value)| (name.to_string(), vec![value.to_string()]))
+                .collect::<HashMap<_, _>>(),
+        );
+        let response = function_handler(&store().await, &Config::default(), request)
+            .await
+            .unwrap();
+        let body = serde_json::from_slice(response.body()).unwrap();
+        (response.status().as_u16(), body)
+    }
+
+    #[tokio::test]
+    async fn rendered_listing_is_sent_as_stored() {
+        let store = store().await;
+        let config = Config::default();
+        let stored: UpcomingLaunches = store.get(&config.launches_file).await.unwrap();
+        store
+            .put_compressed(
+                &rendered_key(&config.launches_file),
+                &UpcomingLaunchesResponse::unfiltered(&stored),
+            )
+            .await
+            .unwrap();
+        let rendered = store
+            .get_bytes(&rendered_key(&config.launches_file))
+            .await
+            .unwrap();
+
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert("accept-encoding", "gzip".parse().unwrap());
+        let response = function_handler(&store, &config, request).await.unwrap();
+        assert_eq!(response.headers()["Content-Encoding"], "gzip");
+        assert_eq!(response.body().to_vec(), rendered);
+
+        // The same document a request rendering it itself gets
+        let plain = function_handler(&store, &config, Request::default())
+            .await
+            .unwrap();
+        let plain: serde_json::Value = serde_json::from_slice(plain.body()).unwrap();
+        let rendered: serde_json::Value = store
+            .get(&rendered_key(&config.launches_file))
+            .await
+            .unwrap();
+        assert_eq!(rendered, plain);
+    }
+
+    fn ids(body: &serde_json::Value) -> Vec<u64> {
+        body["launches"]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|launch| launch["id"].as_u64().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn unfiltered_request_returns_everything_in_order() {
+        let (status, body) = query(&[]).await;
+
+        assert_eq!(status, 200);
+        assert_eq!(ids(&body), vec![1, 2, 3, 4]);
+        assert_eq!(body["date"], "2023-11-04T00:00:00+00:00");
+        assert_eq!(body["next_cursor"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn filters_combine() {
+        let (_, body) = query(&[("provider", "spacex"), ("from", "2023-11-05")]).await;
+        assert_eq!(ids(&body), vec![3]);
+        assert_eq!(body["total_count"], 4);
+        assert_eq!(body["filtered_count"], 1);
+
+        let (_, body) = query(&[("country", "new zealand")]).await;
+        assert_eq!(ids(&body), vec![2]);
+
+        let (_, body) = query(&[("to", "2023-11-04"), ("tag", "crewed")]).await;
+        assert_eq!(ids(&body), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn search_covers_names_and_missions() {
+        let (_, body) = query(&[("q", "CERT-1 PAYLOAD")]).await;
+        assert_eq!(ids(&body), vec![4]);
+    }
+
+    #[tokio::test]
+    async fn cursor_walks_every_page() {
+        let (_, first) = query(&[("limit", "3")]).await;
+        assert_eq!(ids(&first), vec![1, 2, 3]);
+        let cursor = first["next_cursor"].as_str().unwrap().to_string();
+
+        let (_, second) = query(&[("limit", "3"), ("cursor", &cursor)]).await;
+        assert_eq!(ids(&second), vec![4]);
+        assert_eq!(second["next_cursor"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn etag_follows_the_filtered_body() {
+        let store = store().await;
+        let config = Config::default();
+        let get = |etag: Option<&str>, provider: &str| {
+            let mut request = Request::default().with_query_string_parameters(HashMap::from([(
+                "provider".to_string(),
+                provider.to_string(),
+            )]));
+            if let Some(etag) = etag {
+                request
+                    .headers_mut()
+                    .insert("if-none-match", etag.parse().unwrap());
+            }
+            function_handler(&store, &config, request)
+        };
+
+        let spacex = get(None, "spacex").await.unwrap();
+        let etag = spacex.headers()["ETag"].to_str().unwrap().to_string();
+        assert!(spacex.headers().contains_key("Last-Modified"));
+
+        let unchanged = get(Some(&etag), "spacex").await.unwrap();
+        assert_eq!(unchanged.status(), 304);
+        assert!(unchanged.body().is_empty());
+
+        let other_query = get(Some(&etag), "rocket lab").await.unwrap();
+        assert_eq!(other_query.status(), 200);
+        assert_ne!(other_query.headers()["ETag"], etag.as_str());
+    }
+
+    #[tokio::test]
+    async fn exports_a_row_per_launch() {
+        let store = store().await;
+        let request = Request::default().with_query_string_parameters(HashMap::from([
+            ("format".to_string(), "csv".to_string()),
+            ("limit".to_string(), "2".to_string()),
+        ]));
+
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/csv; charset=utf-8"
+        );
+        assert_eq!(response.headers()["Vary"], "Accept, Accept-Encoding");
+        let csv = String::from_utf8(response.body().to_vec()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("id,name,provider,vehicle,pad,"));
+        assert!(lines[1].starts_with("1,"));
+
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert("accept", "application/x-ndjson".parse().unwrap());
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        let ids: Vec<u64> = body
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["id"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+    }
+
+    async fn store_with_changes() -> MemoryStore {
+        let store = store().await;
+        store
+            .put(
+                &Config::default().launch_changes_file,
+                &serde_json::json!({ "changes": [
+                    { "id": 1, "name": "Starlink 6-27", "kind": "added", "detected_at": "2023-11-01T00:00:00Z",
+                      "from": null, "to": { "net": "2023-11-02T08:45:00Z", "win_open": null, "win_close": null, "sort_date": "1698914700" } },
+                    { "id": 9, "name": "Gone", "kind": "removed", "detected_at": "2023-11-01T00:00:00Z",
+                      "from": { "net": null, "win_open": null, "win_close": null, "sort_date": "1698914700" }, "to": null },
+                    { "id": 1, "name": "Starlink 6-27", "kind": "slipped", "detected_at": "2023-11-02T00:00:00Z",
+                      "from": { "net": "2023-11-02T08:45:00Z", "win_open": null, "win_close": null, "sort_date": "1698914700" },
+                      "to": { "net": "2023-11-03T08:45:00Z", "win_open": null, "win_close": null, "sort_date": "1699001100" } },
+                    { "id": 1, "name": "Starlink 6-27", "kind": "updated", "detected_at": "2023-11-02T12:00:00Z",
+                      "from": { "net": "2023-11-03T08:45:00Z", "win_open": null, "win_close": null, "sort_date": "1699001100" },
+                      "to": { "net": "2023-11-03T08:45:00Z", "win_open": null, "win_close": null, "sort_date": "1699001100" } }
+                ]}),
+            )
+            .await
+            .unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn calendar_feed_honours_filters() {
+        let request = http::Request::get("/launches.ics?provider=spacex")
+            .body(Body::Empty)
+            .unwrap()
+            .with_query_string_parameters(HashMap::from([(
+                "provider".to_string(),
+                "spacex".to_string(),
+            )]));
+
+        let response = function_handler(&store_with_changes().await, &Config::default(), request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["content-type"],
+            ics::CALENDAR_CONTENT_TYPE
+        );
+        let body = std::str::from_utf8(response.body()).unwrap();
+        assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert_eq!(body.matches("BEGIN:VEVENT").count(), 2);
+        assert!(body.contains("UID:launch-1@spacebits\r\n"));
+        assert!(body.contains("DTSTART:20231103T084500Z\r\n"));
+        // Launch 1 slipped and was then updated, launch 3 never changed
+        assert!(
+            body.contains("UID:launch-1@spacebits\r\nDTSTAMP:20231101T000000Z\r\nSEQUENCE:2\r\n")
+        );
+        assert!(
+            body.contains("UID:launch-3@spacebits\r\nDTSTAMP:20231103T000000Z\r\nSEQUENCE:0\r\n")
+        );
+        assert!(body
+            .contains("LOCATION:LC-39A\\, Kennedy Space Center\\, Florida\\, United States\r\n"));
+        assert!(!body.contains("Electron"));
+    }
+
+    async fn history(id: &str) -> (u16, serde_json::Value) {
+        let store = store_with_changes().await;
+
+        let request = Request::default()
+            .with_path_parameters(HashMap::from([("id".to_string(), id.to_string())]));
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        let body = serde_json::from_slice(response.body()).unwrap();
+        (response.status().as_u16(), body)
+    }
+
+    #[tokio::test]
+    async fn history_lists_changes_for_one_launch() {
+        let (status, body) = history("1").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["changes"].as_array().unwrap().len(), 3);
+        assert_eq!(body["changes"][1]["kind"], "slipped");
+
+        let (status, body) = history("2").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["changes"], serde_json::json!([]));
+
+        assert_eq!(history("9").await.0, 200);
+        assert_eq!(history("42").await.0, 404);
+        assert_eq!(history("abc").await.0, 400);
+    }
+
+    #[test]
+    fn documented_parameters_are_the_parsed_ones() {
+        // `format` is read by `requested_format` rather than the query
+        let parsed = RefCell::new(BTreeSet::from(["format".to_string()]));
+        LaunchQuery::from_params(&|name| {
+            parsed.borrow_mut().insert(name.to_string());
+            None
+        })
+        .unwrap();
+
+        let document = shared::openapi::document();
+        let documented: BTreeSet<String> = document["paths"]["/launches"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|parameter| parameter["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(documented, parsed.into_inner());
+    }
+
+    #[tokio::test]
+    async fn invalid_parameters_are_bad_requests() {
+        for params in [
+            [("from", "next tuesday")],
+            [("cursor", "abc")],
+            [("suborbital", "maybe")],
+            [("limit", "0")],
+        ] {
+            let (status, _) = query(&params).await;
+            assert_eq!(status, 400, "{:?}", params);
+        }
+    }
+}
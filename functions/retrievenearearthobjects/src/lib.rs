@@ -0,0 +1,659 @@
+use lambda_http::{Body, Error, Request, RequestExt, Response};
+use shared::caching::{cached_response, rendered_response, response_builder, CachePolicy};
+use shared::config::Config;
+use shared::error::{ApiError, PROBLEM_JSON};
+use shared::export::{close_approach_rows, to_csv, to_ndjson, Format};
+use shared::persistencemodels::{
+    neo_archive_key, NearEarthObject, NearEarthObjectApiResponse, NearEarthObjectModel,
+};
+use shared::storage::{DocumentStore, DocumentStoreExt, StorageError};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The feed is collected once a day, and archived days never change
+const CACHE_POLICY: CachePolicy = CachePolicy {
+    max_age: 60 * 60,
+    vary: "Accept, Accept-Encoding",
+};
+
+async fn get_json_data(
+    store: &dyn DocumentStore,
+    key: &str,
+) -> Result<(Arc<NearEarthObjectModel>, Option<SystemTime>), StorageError> {
+    let temp = store.get_cached(key).await?;
+
+    Ok(temp)
+}
+
+/// Day requested through the `{date}` path parameter or `?date=`, if any
+fn requested_date(event: &Request) -> Option<String> {
+    event
+        .path_parameters_ref()
+        .and_then(|params| params.first("date"))
+        .or_else(|| {
+            event
+                .query_string_parameters_ref()
+                .and_then(|params| params.first("date"))
+        })
+        .map(str::to_string)
+}
+
+/// Representation asked for through `?format=` or `Accept`
+fn requested_format(event: &Request) -> Result<Format, String> {
+    let format = event
+        .query_string_parameters_ref()
+        .and_then(|params| params.first("format"));
+    Format::from_request(event, format)
+}
+
+fn error_response(err: ApiError) -> Result<Response<Body>, Error> {
+    tracing::warn!("Request failed: {}", err);
+
+    let resp = response_builder(err.status(), PROBLEM_JSON)
+        .body(err.to_problem_json().into())
+        .map_err(Box::new)?;
+    Ok(resp)
+}
+
+#[derive(Debug, PartialEq)]
+enum SortKey {
+    /// Closest approach first
+    MissDistance,
+    /// Fastest first
+    Velocity,
+    /// Largest first
+    Size,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "miss_distance" => Ok(SortKey::MissDistance),
+            "velocity" => Ok(SortKey::Velocity),
+            "size" => Ok(SortKey::Size),
+            _ => Err(format!(
+                "sort must be one of miss_distance, velocity or size, got {}",
+                value
+            )),
+        }
+    }
+}
+
+/// Filters, ordering and paging requested through the query string
+#[derive(Debug, Default, PartialEq)]
+struct NeoQuery {
+    hazardous: Option<bool>,
+    sentry: Option<bool>,
+    min_diameter_m: Option<f64>,
+    max_miss_distance_lunar: Option<f64>,
+    sort: Option<SortKey>,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+fn query_param<T: FromStr>(event: &Request, name: &str) -> Result<Option<T>, String> {
+    let value = event
+        .query_string_parameters_ref()
+        .and_then(|params| params.first(name));
+
+    match value {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid value for {}: {}", name, value)),
+        None => Ok(None),
+    }
+}
+
+/// Closest approach of the object, in lunar distances
+fn miss_distance_lunar(neo: &NearEarthObject) -> Option<f64> {
+    neo.close_approach_data
+        .iter()
+        .map(|approach| approach.miss_distance.lunar)
+        .min_by(f64::total_cmp)
+}
+
+/// Fastest approach of the object, in km/s
+fn velocity_km_s(neo: &NearEarthObject) -> Option<f64> {
+    neo.close_approach_data
+        .iter()
+        .map(|approach| approach.relative_velocity.kilometers_per_second)
+        .max_by(f64::total_cmp)
+}
+
+/// Upper diameter estimate in meters
+fn diameter_m(neo: &NearEarthObject) -> f64 {
+    neo.estimated_diameter.meters.estimated_diameter_max
+}
+
+impl NeoQuery {
+    fn from_request(event: &Request) -> Result<Self, String> {
+        Ok(NeoQuery {
+            hazardous: query_param(event, "hazardous")?,
+            sentry: query_param(event, "sentry")?,
+            min_diameter_m: query_param(event, "min_diameter_m")?,
+            max_miss_distance_lunar: query_param(event, "max_miss_distance_lunar")?,
+            sort: query_param(event, "sort")?,
+            limit: query_param(event, "limit")?,
+            offset: query_param(event, "offset")?.unwrap_or(0),
+        })
+    }
+
+    fn matches(&self, neo: &NearEarthObject) -> bool {
+        self.hazardous
+            .is_none_or(|hazardous| neo.is_potentially_hazardous_asteroid == hazardous)
+            && self
+                .sentry
+                .is_none_or(|sentry| neo.is_sentry_object == sentry)
+            && self.min_diameter_m.is_none_or(|min| diameter_m(neo) >= min)
+            && self
+                .max_miss_distance_lunar
+                .is_none_or(|max| miss_distance_lunar(neo).is_some_and(|distance| distance <= max))
+    }
+
+    /// Filter and sort the objects, returning the number that matched and the requested page
+    fn apply(&self, objects: &[NearEarthObject]) -> (usize, Vec<NearEarthObject>) {
+        let mut objects: Vec<_> = objects.iter().filter(|neo| self.matches(neo)).collect();
+
+        match self.sort {
+            Some(SortKey::MissDistance) => objects.sort_by(|a, b| {
+                let a = miss_distance_lunar(a).unwrap_or(f64::INFINITY);
+                let b = miss_distance_lunar(b).unwrap_or(f64::INFINITY);
+                a.total_cmp(&b)
+            }),
+            Some(SortKey::Velocity) => objects.sort_by(|a, b| {
+                let a = velocity_km_s(a).unwrap_or(f64::NEG_INFINITY);
+                let b = velocity_km_s(b).unwrap_or(f64::NEG_INFINITY);
+                b.total_cmp(&a)
+            }),
+            Some(SortKey::Size) => objects.sort_by(|a, b| diameter_m(b).total_cmp(&diameter_m(a))),
+            None => {}
+        }
+
+        let filtered_count = objects.len();
+        let page = objects
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+
+        (filtered_count, page)
+    }
+}
+
+/// Filter, sort and page the stored objects as `query` asks
+fn generate_response(
+    stored: &NearEarthObjectModel,
+    query: &NeoQuery,
+) -> NearEarthObjectApiResponse {
+    let (filtered_count, page) = query.apply(&stored.near_earth_objects);
+    NearEarthObjectApiResponse::new(stored, filtered_count, page)
+}
+
+async fn handle_request(
+    store: &dyn DocumentStore,
+    config: &Config,
+    event: &Request,
+) -> Result<(NearEarthObjectApiResponse, Option<SystemTime>), ApiError> {
+    let query = NeoQuery::from_request(event).map_err(ApiError::BadRequest)?;
+
+    // Serve an archived day when one is requested, otherwise the latest snapshot
+    let (data, modified) = match requested_date(event) {
+        Some(date) => {
+            let key = neo_archive_key(&date).ok_or_else(|| {
+                ApiError::BadRequest("date must be formatted as YYYY-MM-DD".to_string())
+            })?;
+            get_json_data(store, &key).await.map_err(|err| match err {
+                StorageError::NotFound(_) => {
+                    ApiError::NotFound(format!("No data archived for {}", date))
+                }
+                err => err.into(),
+            })?
+        }
+        None => get_json_data(store, &config.neo_file).await?,
+    };
+
+    Ok((generate_response(&data, &query), modified))
+}
+
+pub async fn function_handler(
+    store: &dyn DocumentStore,
+    config: &Config,
+    event: Request,
+) -> Result<Response<Body>, Error> {
+    let format = match requested_format(&event) {
+        Ok(format) => format,
+        Err(err) => return error_response(ApiError::BadRequest(err)),
+    };
+
+    // The latest snapshot as a whole is stored ready to send by the collector
+    let unfiltered =
+        event.query_string_parameters_ref().is_none() && requested_date(&event).is_none();
+    if unfiltered && format == Format::Json {
+        let content_type = format.content_type();
+        match rendered_response(store, &event, &config.neo_file, content_type, CACHE_POLICY).await {
+            Ok(Some(response)) => return Ok(response),
+            Ok(None) => {}
+            Err(err) => tracing::warn!("Could not read the rendered response: {}", err),
+        }
+    }
+
+    let (response, modified) = match handle_request(store, config, &event).await {
+        Ok(response) => response,
+        Err(err) => return error_response(err),
+    };
+
+    // CSV and NDJSON have a row per close approach of the requested page
+    let objects = &response.data.near_earth_objects;
+    let body = match format {
+        Format::Json => serde_json::to_string(&response).map_err(|err| err.to_string()),
+        Format::Csv => to_csv(&close_approach_rows(objects)).map_err(|err| err.to_string()),
+        Format::Ndjson => to_ndjson(&close_approach_rows(objects)).map_err(|err| err.to_string()),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(err) => {
+            return error_response(ApiError::CorruptDocument(format!(
+                "Could not export the stored data as {}: {}",
+                format.content_type(),
+                err
+            )))
+        }
+    };
+
+    cached_response(&event, format.content_type(), body, modified, CACHE_POLICY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::storage::{rendered_key, MemoryStore};
+    use std::collections::HashMap;
+    use std::io::Read;
+
+    fn model(updated_date_time: &str) -> serde_json::Value {
+        serde_json::json!({
+            "links": { "next": null, "prev": null, "self": null },
+            "element_count": 0,
+            "updated_date_time": updated_date_time,
+            "near_earth_objects": []
+        })
+    }
+
+    fn request_for_date(date: &str) -> Request {
+        Request::default()
+            .with_query_string_parameters(HashMap::from([("date".to_string(), date.to_string())]))
+    }
+
+    async fn store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store
+            .put(&Config::default().neo_file, &model("latest"))
+            .await
+            .unwrap();
+        store
+            .put("neo/2023/11/01.json", &model("archived"))
+            .await
+            .unwrap();
+        store
+    }
+
+    fn updated(response: &Response<Body>) -> String {
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        body["updated_date_time"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn serves_latest_without_date() {
+        let response = function_handler(&store().await, &Config::default(), Request::default())
+            .await
+            .unwrap();
+        assert_eq!(updated(&response), "latest");
+    }
+
+    #[tokio::test]
+    async fn archived_day_is_not_modified_since_it_was_written() {
+        let store = store().await;
+        let response = function_handler(&store, &Config::default(), request_for_date("2023-11-01"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=3600");
+
+        let mut request = request_for_date("2023-11-01");
+        request.headers_mut().insert(
+            "if-modified-since",
+            response.headers()["Last-Modified"].clone(),
+        );
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+        assert!(response.body().is_empty());
+    }
+
+    #[tokio::test]
+    async fn serves_archived_day() {
+        let response = function_handler(
+            &store().await,
+            &Config::default(),
+            request_for_date("2023-11-01"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(updated(&response), "archived");
+    }
+
+    #[tokio::test]
+    async fn missing_day_is_not_found() {
+        let response = function_handler(
+            &store().await,
+            &Config::default(),
+            request_for_date("2023-11-02"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn malformed_date_is_bad_request() {
+        let response = function_handler(
+            &store().await,
+            &Config::default(),
+            request_for_date("yesterday"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    fn neo(
+        id: &str,
+        hazardous: bool,
+        diameter_m: f64,
+        lunar: &str,
+        km_s: &str,
+    ) -> serde_json::Value {
+        let diameter = serde_json::json!({
+            "estimated_diameter_min": diameter_m / 2.0,
+            "estimated_diameter_max": diameter_m
+        });
+
+        serde_json::json!({
+            "id": id,
+            "neo_reference_id": id,
+            "name": id,
+            "nasa_jpl_url": "https://ssd.jpl.nasa.gov/",
+            "absolute_magnitude_h": 20.0,
+            "estimated_diameter": {
+                "kilometers": diameter, "meters": diameter, "miles": diameter, "feet": diameter
+            },
+            "is_potentially_hazardous_asteroid": hazardous,
+            "close_approach_data": [{
+                "close_approach_date": "2023-11-01",
+                "epoch_date_close_approach": 1698796800000i64,
+                "relative_velocity": {
+                    "kilometers_per_second": km_s,
+                    "kilometers_per_hour": "0",
+                    "miles_per_hour": "0"
+                },
+                "miss_distance": {
+                    "astronomical": "0",
+                    "lunar": lunar,
+                    "kilometers": "0",
+                    "miles": "0"
+                },
+                "orbiting_body": "Earth"
+            }],
+            "is_sentry_object": false,
+            "links": { "next": null, "prev": null, "self": null }
+        })
+    }
+
+    fn request_with(params: &[(&str, &str)]) -> Request {
+        Request::default().with_query_string_parameters(
+            params
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    async fn populated_store() -> MemoryStore {
+        let mut document = model("latest");
+        document["near_earth_objects"] = serde_json::json!([
+            neo("small-near", false, 20.0, "2.5", "10.0"),
+            neo("big-far", true, 900.0, "80.0", "30.0"),
+            neo("mid-near", true, 150.0, "1.5", "20.0"),
+        ]);
+
+        let store = MemoryStore::new();
+        store
+            .put(&Config::default().neo_file, &document)
+            .await
+            .unwrap();
+        store
+    }
+
+    async fn ids_for(params: &[(&str, &str)]) -> (serde_json::Value, Vec<String>) {
+        let response = function_handler(
+            &populated_store().await,
+            &Config::default(),
+            request_with(params),
+        )
+        .await
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let ids = body["data"]["near_earth_objects"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|neo| neo["id"].as_str().unwrap().to_string())
+            .collect();
+        (body, ids)
+    }
+
+    #[tokio::test]
+    async fn filters_hazardous_and_reports_counts() {
+        let (body, ids) = ids_for(&[("hazardous", "true")]).await;
+        assert_eq!(ids, vec!["big-far", "mid-near"]);
+        assert_eq!(body["total_count"], 3);
+        assert_eq!(body["filtered_count"], 2);
+    }
+
+    #[tokio::test]
+    async fn filters_by_size_and_miss_distance() {
+        let (_, ids) =
+            ids_for(&[("min_diameter_m", "100"), ("max_miss_distance_lunar", "10")]).await;
+        assert_eq!(ids, vec!["mid-near"]);
+    }
+
+    #[tokio::test]
+    async fn sorts_numerically_and_pages() {
+        let (_, ids) = ids_for(&[("sort", "miss_distance")]).await;
+        assert_eq!(ids, vec!["mid-near", "small-near", "big-far"]);
+
+        let (body, ids) = ids_for(&[("sort", "velocity"), ("limit", "1"), ("offset", "1")]).await;
+        assert_eq!(ids, vec!["mid-near"]);
+        assert_eq!(body["filtered_count"], 3);
+    }
+
+    #[tokio::test]
+    async fn responses_are_compressed_when_accepted() {
+        let store = populated_store().await;
+        let plain = function_handler(&store, &Config::default(), request_with(&[]))
+            .await
+            .unwrap();
+        assert!(plain.headers().get("Content-Encoding").is_none());
+
+        let mut request = request_with(&[]);
+        request
+            .headers_mut()
+            .insert("accept-encoding", "gzip, deflate".parse().unwrap());
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["Content-Encoding"], "gzip");
+        assert_eq!(response.headers()["Vary"], "Accept, Accept-Encoding");
+        assert!(matches!(response.body(), Body::Binary(_)));
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&response.body()[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain.body().to_vec());
+        let etag = response.headers()["ETag"].to_str().unwrap();
+        assert_eq!(
+            etag,
+            format!("W/{}", plain.headers()["ETag"].to_str().unwrap())
+        );
+
+        let mut request = request_with(&[]);
+        request
+            .headers_mut()
+            .insert("accept-encoding", "br".parse().unwrap());
+        request
+            .headers_mut()
+            .insert("if-none-match", etag.parse().unwrap());
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+    }
+
+    #[tokio::test]
+    async fn rendered_snapshot_is_sent_as_stored() {
+        let store = populated_store().await;
+        let config = Config::default();
+        let stored: NearEarthObjectModel = store.get(&config.neo_file).await.unwrap();
+        store
+            .put_compressed(
+                &rendered_key(&config.neo_file),
+                &NearEarthObjectApiResponse::unfiltered(&stored),
+            )
+            .await
+            .unwrap();
+        let rendered = store
+            .get_bytes(&rendered_key(&config.neo_file))
+            .await
+            .unwrap();
+
+        let mut request = request_with(&[]);
+        request
+            .headers_mut()
+            .insert("accept-encoding", "gzip".parse().unwrap());
+        let response = function_handler(&store, &config, request).await.unwrap();
+        assert_eq!(response.headers()["Content-Encoding"], "gzip");
+        assert_eq!(response.body().to_vec(), rendered);
+
+        // The same document a request rendering it itself gets
+        let plain = function_handler(&store, &config, request_with(&[]))
+            .await
+            .unwrap();
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&rendered[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain.body().to_vec());
+
+        let mut request = request_with(&[("hazardous", "true")]);
+        request
+            .headers_mut()
+            .insert("accept-encoding", "gzip".parse().unwrap());
+        let filtered = function_handler(&store, &config, request).await.unwrap();
+        assert_ne!(filtered.body().to_vec(), rendered);
+    }
+
+    #[tokio::test]
+    async fn exports_a_row_per_close_approach() {
+        let store = populated_store().await;
+
+        let response = function_handler(
+            &store,
+            &Config::default(),
+            request_with(&[("format", "csv"), ("hazardous", "true")]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/csv; charset=utf-8"
+        );
+        let csv = String::from_utf8(response.body().to_vec()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("id,name,close_approach_date,"));
+        assert!(lines[1].starts_with("big-far,big-far,2023-11-01,Earth,"));
+
+        let mut request = request_with(&[("sort", "miss_distance")]);
+        request
+            .headers_mut()
+            .insert("accept", "application/x-ndjson".parse().unwrap());
+        let response = function_handler(&store, &Config::default(), request)
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        let rows: Vec<serde_json::Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["id"], "mid-near");
+        assert_eq!(rows[0]["miss_distance_lunar"], 1.5);
+        assert_eq!(rows[0]["estimated_diameter_max_m"], 150.0);
+
+        let response = function_handler(
+            &store,
+            &Config::default(),
+            request_with(&[("format", "xml")]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn invalid_query_is_bad_request() {
+        let response = function_handler(
+            &populated_store().await,
+            &Config::default(),
+            request_with(&[("sort", "name")]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn missing_document_is_problem_json_with_cors() {
+        let response =
+            function_handler(&MemoryStore::new(), &Config::default(), Request::default())
+                .await
+                .unwrap();
+
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.headers()["content-type"], PROBLEM_JSON);
+        assert_eq!(response.headers()["Access-Control-Allow-Origin"], "*");
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["status"], 404);
+    }
+
+    #[tokio::test]
+    async fn corrupt_document_is_server_error() {
+        let store = MemoryStore::new();
+        store
+            .put_bytes(&Config::default().neo_file, b"not json".to_vec())
+            .await
+            .unwrap();
+
+        let response = function_handler(&store, &Config::default(), Request::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 500);
+    }
+}
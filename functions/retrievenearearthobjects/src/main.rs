@@ -1,46 +1,7 @@
-use lambda_http::{run, service_fn, Body, Error, Request, RequestExt, Response};
-use shared::persistencemodels::{
-    NearEarthObject, NearEarthObjectApiResponse, NearEarthObjectModel,
-};
-
-async fn get_json_data_from_s3() -> Result<NearEarthObjectModel, Error> {
-    let config = aws_config::load_from_env().await;
-    let client = aws_sdk_s3::Client::new(&config);
-    let data = client
-        .get_object()
-        .bucket("spaceclouddatabucket")
-        .key("near_earth_objects.json")
-        .send()
-        .await?;
-
-    let bytes = data.body.collect().await?.into_bytes();
-    let response = std::str::from_utf8(&bytes)?;
-    let temp: NearEarthObjectModel = serde_json::from_str(response).unwrap();
-
-    Ok(temp)
-}
-
-fn generate_response(data: NearEarthObjectModel) -> NearEarthObjectApiResponse {
-    NearEarthObjectApiResponse {
-        updated_date_time: data.updated_date_time.clone(),
-        data,
-    }
-}
-
-async fn function_handler(_event: Request) -> Result<Response<Body>, Error> {
-    let data = get_json_data_from_s3().await.unwrap();
-    let response = generate_response(data);
-
-    let resp = Response::builder()
-        .status(200)
-        .header("content-type", "application/json")
-        .header("Access-Control-Allow-Origin", "*")
-        .header("Access-Control-Allow-Headers", "*")
-        .header("Access-Control-Allow-Methods", "GET")
-        .body(serde_json::to_string(&response).unwrap_or_default().into())
-        .map_err(Box::new)?;
-    Ok(resp)
-}
+use lambda_http::{run, service_fn, Error};
+use retrievenearearthobjects::function_handler;
+use shared::config::Config;
+use shared::storage;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -52,5 +13,13 @@ async fn main() -> Result<(), Error> {
         .without_time()
         .init();
 
-    run(service_fn(function_handler)).await
+    let config = Config::from_env()?;
+    let store = storage::from_config(&config).await;
+    let store = store.as_ref();
+    let config = &config;
+
+    run(service_fn(move |event| {
+        function_handler(store, config, event)
+    }))
+    .await
 }
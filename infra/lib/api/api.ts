@@ -8,6 +8,9 @@ export function createRestApi(stack: cdk.Stack): cdk.aws_apigateway.RestApi {
 		retainDeployments: false,
 		endpointExportName: "SpaceBitsApiEndpoint",
 		deploy: true,
+		// Compressed responses come back from the lambdas base64 encoded, only
+		// in the types the read endpoints compress
+		binaryMediaTypes: ["application/json", "text/csv", "application/x-ndjson", "text/calendar"],
 		endpointConfiguration: {
 			types: [cdk.aws_apigateway.EndpointType.REGIONAL],
 		},
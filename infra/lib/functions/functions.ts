@@ -26,7 +26,7 @@ export function getUpcomingLaunchJsonForApi(stack: cdk.Stack, role: Role, bucket
 		logRetention: cdk.aws_logs.RetentionDays.ONE_DAY,
 		environment: {
 			BUCKET_NAME: bucketName,
-			FILE_NAME: "launches.json",
+			LAUNCHES_FILE: "launches.json",
 		},
 	});
 }
@@ -56,7 +56,7 @@ export function getAndStorePeopleInSpaceFunction(stack: cdk.Stack, role: Role, b
 		description: "This function gets the people in space data and stores it in the bucket.",
 		environment: {
 			BUCKET_NAME: bucketName,
-			FILE_NAME: "people_in_space.json",
+			PEOPLE_FILE: "people_in_space.json",
 		},
 	});
 }
@@ -86,12 +86,63 @@ export function nearEarthObjectsRetrievalFunction(stack: cdk.Stack, role: Role,
 		fileName: "readnearearthobjects",
 		environment: {
 			BUCKET_NAME: bucketName,
-			FILE_NAME: "near_earth_objects.json",
-			KEY_LOCATION: "/space_cloud/keys/nasa_api_key",
+			NEO_FILE: "near_earth_objects.json",
+			NASA_KEY_LOCATION: "/space_cloud/keys/nasa_api_key",
 		},
 	});
 }
 
+// Get and store upcoming launches
+export function collectUpcomingLaunchesFunction(stack: cdk.Stack, role: Role, bucketName: string) {
+	return createRustLambdaFunctionArm64({
+		id: "CollectUpcomingLaunchesFunction",
+		stack: stack,
+		role: role,
+		bucketName: bucketName,
+		functionName: "collect-upcoming-launches",
+		fileName: "collectupcominglaunches",
+		description: "This function gets the upcoming launches and stores them in the bucket.",
+		environment: {
+			BUCKET_NAME: bucketName,
+			LAUNCHES_FILE: "launches.json",
+			LAUNCH_KEY_LOCATION: "/space_cloud/keys/rocket_launch_live_api_key",
+		},
+	});
+}
+
+// Deliver webhook notifications for space events
+export function dispatchNotificationsFunction(stack: cdk.Stack, role: Role, bucketName: string) {
+	return createRustLambdaFunctionArm64({
+		id: "DispatchNotificationsFunction",
+		stack: stack,
+		role: role,
+		bucketName: bucketName,
+		functionName: "dispatch-notifications",
+		fileName: "dispatchnotifications",
+		description: "This function delivers webhook notifications for hazardous NEOs, crew changes and launches.",
+		environment: {
+			BUCKET_NAME: bucketName,
+			LAUNCHES_FILE: "launches.json",
+			NEO_FILE: "near_earth_objects.json",
+			NOTIFY_SUBSCRIPTIONS_FILE: "notify/subscriptions.json",
+			NOTIFY_LEDGER_FILE: "notify/delivered.json",
+			NEO_ALERT_LUNAR_DISTANCE: "10",
+		},
+	});
+}
+
+// Serve the OpenAPI document of the read endpoints
+export function readOpenApiFunction(stack: cdk.Stack, role: Role) {
+	return createRustLambdaFunctionArm64({
+		id: "ReadOpenApiFunction",
+		stack: stack,
+		role: role,
+		functionName: "read-openapi",
+		fileName: "readopenapi",
+		description: "This function serves the OpenAPI 3.1 description of the read endpoints.",
+	});
+}
+
 function createRustLambdaFunctionArm64(props: CreateRustLambdaFunctionArm64Props) {
 	return new cdk.aws_lambda.Function(props.stack, props.id, {
 		functionName: `${props.stack.stackName}-${props.functionName}`,
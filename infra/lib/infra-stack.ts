@@ -43,6 +43,23 @@ export class SpaceRustStack extends cdk.Stack {
 			BUCKET_NAME,
 		);
 
+		// Get and store upcoming launches
+		const collectUpcomingLaunchesFunction = functions.collectUpcomingLaunchesFunction(
+			this,
+			spaceBitsLambdaRole,
+			BUCKET_NAME,
+		);
+
+		// Deliver webhook notifications to subscribers
+		const dispatchNotificationsFunction = functions.dispatchNotificationsFunction(
+			this,
+			spaceBitsLambdaRole,
+			BUCKET_NAME,
+		);
+
+		// Serve the OpenAPI description of the read endpoints
+		const readOpenApiFunction = functions.readOpenApiFunction(this, spaceBitsLambdaRole);
+
 		// Get existing bucket
 		const bucket = cdk.aws_s3.Bucket.fromBucketName(this, "SpaceCloudBucket", "spaceclouddatabucket");
 
@@ -51,6 +68,8 @@ export class SpaceRustStack extends cdk.Stack {
 		bucket.grantRead(retrieveNearEarthObjectsFunction);
 		bucket.grantRead(getUpcomingLaunchJsonForApi);
 		bucket.grantReadWrite(nearEarthObjectsRetrievalFunction);
+		bucket.grantReadWrite(collectUpcomingLaunchesFunction);
+		bucket.grantReadWrite(dispatchNotificationsFunction);
 
 		// Api Gateway
 		const spaceBitsApi = api.createRestApi(this);
@@ -61,6 +80,16 @@ export class SpaceRustStack extends cdk.Stack {
 			apiKeyRequired: false,
 		});
 
+		// Arrival and departure history, and the history of a single person
+		const readPeopleHistoryResource = readPeopleResource.addResource("history");
+		readPeopleHistoryResource.addMethod("GET", new cdk.aws_apigateway.LambdaIntegration(readFunction), {
+			apiKeyRequired: false,
+		});
+		const readPersonResource = readPeopleResource.addResource("{name}");
+		readPersonResource.addMethod("GET", new cdk.aws_apigateway.LambdaIntegration(readFunction), {
+			apiKeyRequired: false,
+		});
+
 		// Retrieve Near Earth Objects endpoint
 		const retrieveNearEarthObjectsResource = spaceBitsApi.root.addResource("neo");
 		retrieveNearEarthObjectsResource.addMethod(
@@ -71,6 +100,16 @@ export class SpaceRustStack extends cdk.Stack {
 			},
 		);
 
+		// Retrieve archived Near Earth Objects for a single day
+		const retrieveArchivedNearEarthObjectsResource = retrieveNearEarthObjectsResource.addResource("{date}");
+		retrieveArchivedNearEarthObjectsResource.addMethod(
+			"GET",
+			new cdk.aws_apigateway.LambdaIntegration(retrieveNearEarthObjectsFunction),
+			{
+				apiKeyRequired: true,
+			},
+		);
+
 		// Get upcoming launches endpoint
 		const retrieveUpcomingLaunchesResource = spaceBitsApi.root.addResource("upcomingLaunches");
 		retrieveUpcomingLaunchesResource.addMethod(
@@ -81,6 +120,38 @@ export class SpaceRustStack extends cdk.Stack {
 			},
 		);
 
+		// Upcoming launches as an iCalendar feed
+		const upcomingLaunchesCalendarResource = spaceBitsApi.root.addResource("launches.ics");
+		upcomingLaunchesCalendarResource.addMethod(
+			"GET",
+			new cdk.aws_apigateway.LambdaIntegration(getUpcomingLaunchJsonForApi),
+			{
+				apiKeyRequired: false,
+			},
+		);
+
+		// Upcoming launches under the path the OpenAPI document describes
+		const launchesResource = spaceBitsApi.root.addResource("launches");
+		launchesResource.addMethod("GET", new cdk.aws_apigateway.LambdaIntegration(getUpcomingLaunchJsonForApi), {
+			apiKeyRequired: false,
+		});
+
+		// Schedule change history of a single launch
+		const launchHistoryResource = launchesResource.addResource("{id}").addResource("history");
+		launchHistoryResource.addMethod(
+			"GET",
+			new cdk.aws_apigateway.LambdaIntegration(getUpcomingLaunchJsonForApi),
+			{
+				apiKeyRequired: false,
+			},
+		);
+
+		// OpenAPI document
+		const openApiResource = spaceBitsApi.root.addResource("openapi.json");
+		openApiResource.addMethod("GET", new cdk.aws_apigateway.LambdaIntegration(readOpenApiFunction), {
+			apiKeyRequired: false,
+		});
+
 		// API usage plan
 		const spaceBitsApiUsagePlan = api.createApiUsagePlan(this, spaceBitsApi);
 
@@ -103,6 +174,13 @@ export class SpaceRustStack extends cdk.Stack {
 		//      }
 		//    );
 
+		// Event to run every fifteen minutes, often enough to catch launches an hour out
+		const everyFifteenMinutesEventRule = new cdk.aws_events.Rule(this, "everyFifteenMinutesEventRule", {
+			schedule: cdk.aws_events.Schedule.rate(cdk.Duration.minutes(15)),
+			enabled: true,
+			ruleName: "everyFifteenMinutesEventRule",
+		});
+
 		// Event to run daily
 		const dailyEventRule = new cdk.aws_events.Rule(this, "dailyEventRule", {
 			schedule: cdk.aws_events.Schedule.cron({
@@ -114,7 +192,9 @@ export class SpaceRustStack extends cdk.Stack {
 
 		// Add targets to event rules
 		everyTwoHoursEventRule.addTarget(new cdk.aws_events_targets.LambdaFunction(getAndStorePeopleInSpaceFunction));
+		everyTwoHoursEventRule.addTarget(new cdk.aws_events_targets.LambdaFunction(collectUpcomingLaunchesFunction));
 		dailyEventRule.addTarget(new cdk.aws_events_targets.LambdaFunction(nearEarthObjectsRetrievalFunction));
+		everyFifteenMinutesEventRule.addTarget(new cdk.aws_events_targets.LambdaFunction(dispatchNotificationsFunction));
 
 		// Key for API usage plan
 		const apiKey = spaceBitsApi.addApiKey("SpaceBitsApiKey");
@@ -1,3 +1,5 @@
+use crate::craft::{Craft, CraftInfo};
+use crate::history::PeopleStats;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -41,7 +43,7 @@ pub struct NearEarthLink {
     pub this: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiResponseLink {
     pub next: Option<String>,
     pub prev: Option<String>,
@@ -86,3 +88,55 @@ pub struct MissDistance {
     pub kilometers: String,
     pub miles: String,
 }
+
+// People API responses
+
+/// People sharing a craft, with whatever the registry knows about it
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct CraftGroup<'a> {
+    pub name: &'a str,
+    #[serde(flatten)]
+    pub info: Option<&'static CraftInfo>,
+    pub people: Vec<&'a str>,
+}
+
+/// A person with the time they have been tracked in orbit, in whole days
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct EnrichedPerson<'a> {
+    pub name: &'a str,
+    pub craft: &'a Craft,
+    pub days_in_orbit_current_stint: Option<i64>,
+    pub tracked_days: Option<i64>,
+}
+
+/// Body of the `/people` response
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct PeopleByCraftResponse<'a> {
+    pub update_time: &'a str,
+    pub people: Vec<EnrichedPerson<'a>>,
+    pub crafts: Vec<CraftGroup<'a>>,
+    pub stats: Option<PeopleStats>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn craft_groups_name_the_craft_once() {
+        let craft = Craft::from_name("ISS");
+        let group = CraftGroup {
+            name: craft.name(),
+            info: craft.info(),
+            people: vec!["Jasmin Moghbeli"],
+        };
+
+        assert_eq!(
+            serde_json::to_string(&group).unwrap(),
+            r#"{"name":"ISS","operator":"NASA, Roscosmos, ESA, JAXA, CSA","kind":"station","launched":"1998-11-20","people":["Jasmin Moghbeli"]}"#
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Validators and freshness headers for conditional GETs on the read endpoints
+use crate::compression::Encoding;
+#[cfg(feature = "storage")]
+use crate::storage::{is_gzip, rendered_key, DocumentStore, StorageError};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The same alias as `lambda_http::Error`, so handlers can use `?` on it
+pub type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// CORS headers sent by every read endpoint, on success and error alike
+pub const CORS_HEADERS: [(&str, &str); 3] = [
+    ("Access-Control-Allow-Origin", "*"),
+    ("Access-Control-Allow-Headers", "*"),
+    ("Access-Control-Allow-Methods", "GET"),
+];
+
+/// Strong entity tag derived from the response body
+pub fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("\"{}\"", hex::encode(&digest[..16]))
+}
+
+/// `Cache-Control` allowing clients and shared caches to reuse a response
+/// for `max_age` seconds
+pub fn cache_control(max_age: u64) -> String {
+    format!("public, max-age={}", max_age)
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// What a response is validated against: the hash of its body, and when the
+/// documents it was built from were last written, if the store knows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validators {
+    pub etag: String,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    pub fn new(body: &[u8], last_modified: Option<SystemTime>) -> Self {
+        Validators {
+            etag: etag(body),
+            last_modified,
+        }
+    }
+
+    /// Validators for a compressed copy of the body: its bytes differ from
+    /// the ones hashed, so only a weak tag still describes it
+    pub fn weak(self) -> Self {
+        Validators {
+            etag: format!("W/{}", self.etag.trim_start_matches("W/")),
+            ..self
+        }
+    }
+
+    /// Whether a GET carrying these conditional headers can be answered with
+    /// 304; `If-Modified-Since` only counts without `If-None-Match` (RFC 9110)
+    pub fn not_modified(
+        &self,
+        if_none_match: Option<&str>,
+        if_modified_since: Option<&str>,
+    ) -> bool {
+        if let Some(if_none_match) = if_none_match {
+            return if_none_match.split(',').map(str::trim).any(|tag| {
+                tag == "*" || tag.trim_start_matches("W/") == self.etag.trim_start_matches("W/")
+            });
+        }
+
+        let since = if_modified_since.and_then(|since| httpdate::parse_http_date(since).ok());
+        match (self.last_modified, since) {
+            // HTTP dates only have whole seconds
+            (Some(modified), Some(since)) => unix_seconds(modified) <= unix_seconds(since),
+            _ => false,
+        }
+    }
+
+    /// `ETag`, `Last-Modified` and `Cache-Control`, sent on both 200 and 304
+    pub fn headers(&self, max_age: u64) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("ETag", self.etag.clone())];
+        if let Some(modified) = self.last_modified {
+            headers.push(("Last-Modified", httpdate::fmt_http_date(modified)));
+        }
+        headers.push(("Cache-Control", cache_control(max_age)));
+        headers
+    }
+}
+
+/// How long a response may be reused, and the request headers that select
+/// between its representations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachePolicy {
+    pub max_age: u64,
+    pub vary: &'static str,
+}
+
+/// Builder carrying the CORS headers shared by success and error responses
+pub fn response_builder(status: u16, content_type: &str) -> http::response::Builder {
+    CORS_HEADERS.into_iter().fold(
+        http::Response::builder()
+            .status(status)
+            .header("content-type", content_type),
+        |builder, (name, value)| builder.header(name, value),
+    )
+}
+
+/// 200 with the body, compressed when the client accepts it, or 304 without
+/// it when the client's copy is current
+///
+/// `B` is the handler's body type, `lambda_http::Body` in the lambdas.
+pub fn cached_response<R, B>(
+    request: &http::Request<R>,
+    content_type: &str,
+    body: String,
+    last_modified: Option<SystemTime>,
+    policy: CachePolicy,
+) -> Result<http::Response<B>, Error>
+where
+    B: Default + From<String> + From<Vec<u8>>,
+{
+    let encoding = Encoding::for_response(header(request, "accept-encoding"), body.len());
+    let mut validators = Validators::new(body.as_bytes(), last_modified);
+    if encoding != Encoding::Identity {
+        validators = validators.weak();
+    }
+
+    respond(request, content_type, validators, policy, encoding, || {
+        // lambda_http passes binary bodies to API Gateway base64 encoded
+        Ok(match encoding {
+            Encoding::Identity => body.into(),
+            encoding => encoding.encode(body.as_bytes())?.into(),
+        })
+    })
+}
+
+/// [`cached_response`] for a body stored already compressed with
+/// `encoding`, which the caller has checked the client accepts
+pub fn encoded_response<R, B>(
+    request: &http::Request<R>,
+    content_type: &str,
+    body: Vec<u8>,
+    encoding: Encoding,
+    last_modified: Option<SystemTime>,
+    policy: CachePolicy,
+) -> Result<http::Response<B>, Error>
+where
+    B: Default + From<String> + From<Vec<u8>>,
+{
+    let validators = Validators::new(&body, last_modified).weak();
+    respond(request, content_type, validators, policy, encoding, || {
+        Ok(body.into())
+    })
+}
+
+/// The collector's gzip copy of the response to a request without query
+/// parameters, passed through as stored, or `None` when the client does not
+/// take gzip or the copy is missing or older than the document at `key`
+#[cfg(feature = "storage")]
+pub async fn rendered_response<R, B>(
+    store: &dyn DocumentStore,
+    request: &http::Request<R>,
+    key: &str,
+    content_type: &str,
+    policy: CachePolicy,
+) -> Result<Option<http::Response<B>>, Error>
+where
+    B: Default + From<String> + From<Vec<u8>>,
+{
+    if !Encoding::Gzip.accepts(header(request, "accept-encoding")) {
+        return Ok(None);
+    }
+    let (body, rendered) = match store.get_bytes_with_meta(&rendered_key(key)).await {
+        Ok(rendered) => rendered,
+        Err(StorageError::NotFound(_)) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let source = store.meta(key).await?;
+    let current = matches!(
+        (rendered.last_modified, source.last_modified),
+        (Some(rendered), Some(source)) if rendered >= source
+    );
+    if !current || !is_gzip(&body) {
+        return Ok(None);
+    }
+
+    encoded_response(
+        request,
+        content_type,
+        body,
+        Encoding::Gzip,
+        source.last_modified,
+        policy,
+    )
+    .map(Some)
+}
+
+fn header<'a, R>(request: &'a http::Request<R>, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// 200 with the body `encoded` produces, or 304 without it when the
+/// client's copy is current
+fn respond<R, B>(
+    request: &http::Request<R>,
+    content_type: &str,
+    validators: Validators,
+    policy: CachePolicy,
+    encoding: Encoding,
+    encoded: impl FnOnce() -> Result<B, Error>,
+) -> Result<http::Response<B>, Error>
+where
+    B: Default,
+{
+    let not_modified = validators.not_modified(
+        header(request, "if-none-match"),
+        header(request, "if-modified-since"),
+    );
+
+    let mut builder = response_builder(if not_modified { 304 } else { 200 }, content_type)
+        .header("Vary", policy.vary);
+    for (name, value) in validators.headers(policy.max_age) {
+        builder = builder.header(name, value);
+    }
+    let body = if not_modified {
+        B::default()
+    } else {
+        if let Some(content_encoding) = encoding.header_value() {
+            builder = builder.header("Content-Encoding", content_encoding);
+        }
+        encoded()?
+    };
+
+    Ok(builder.body(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn modified() -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(1_698_840_000_500)
+    }
+
+    fn validators() -> Validators {
+        Validators::new(b"{\"people\":[]}", Some(modified()))
+    }
+
+    #[test]
+    fn etag_is_a_quoted_body_hash() {
+        let tag = etag(b"{}");
+        assert_eq!(tag, etag(b"{}"));
+        assert_ne!(tag, etag(b"[]"));
+        assert_eq!(tag.len(), 34);
+        assert!(tag.starts_with('"') && tag.ends_with('"'));
+    }
+
+    #[test]
+    fn if_none_match_compares_tags() {
+        let validators = validators();
+        let weak = format!("W/{}", validators.etag);
+        let list = format!("\"other\", {}", validators.etag);
+
+        assert!(validators.not_modified(Some(&validators.etag), None));
+        assert!(validators.not_modified(Some(&weak), None));
+        assert!(validators.not_modified(Some(&list), None));
+        assert!(validators.not_modified(Some("*"), None));
+        assert!(!validators.not_modified(Some("\"other\""), None));
+
+        let compressed = validators.clone().weak();
+        assert_eq!(compressed.etag, weak);
+        assert!(compressed.not_modified(Some(&weak), None));
+        assert!(compressed.not_modified(Some(&validators.etag), None));
+    }
+
+    #[test]
+    fn if_modified_since_compares_whole_seconds() {
+        let validators = validators();
+        let same_second = httpdate::fmt_http_date(modified());
+        let earlier = httpdate::fmt_http_date(modified() - Duration::from_secs(60));
+
+        assert!(validators.not_modified(None, Some(&same_second)));
+        assert!(!validators.not_modified(None, Some(&earlier)));
+        assert!(!validators.not_modified(None, Some("yesterday")));
+        assert!(!Validators::new(b"", None).not_modified(None, Some(&same_second)));
+        // A changed body wins over an unchanged date
+        assert!(!validators.not_modified(Some("\"other\""), Some(&same_second)));
+    }
+
+    #[test]
+    fn cached_responses_carry_validators_and_cors_headers() {
+        const POLICY: CachePolicy = CachePolicy {
+            max_age: 60,
+            vary: "Accept-Encoding",
+        };
+        let body = "{\"people\":[]}".repeat(100);
+        let respond = |request: http::Request<()>| -> http::Response<Vec<u8>> {
+            cached_response(&request, "application/json", body.clone(), None, POLICY).unwrap()
+        };
+
+        let response = respond(http::Request::new(()));
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), body.as_bytes());
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=60");
+        assert_eq!(response.headers()["Vary"], "Accept-Encoding");
+        for (name, value) in CORS_HEADERS {
+            assert_eq!(response.headers()[name], value);
+        }
+
+        let request = http::Request::builder()
+            .header("accept-encoding", "gzip")
+            .body(())
+            .unwrap();
+        let compressed = respond(request);
+        assert_eq!(compressed.headers()["Content-Encoding"], "gzip");
+        assert!(compressed.body().len() < body.len());
+
+        let request = http::Request::builder()
+            .header("if-none-match", &response.headers()["ETag"])
+            .body(())
+            .unwrap();
+        let not_modified = respond(request);
+        assert_eq!(not_modified.status(), 304);
+        assert!(not_modified.body().is_empty());
+    }
+
+    #[cfg(feature = "storage")]
+    #[tokio::test]
+    async fn rendered_responses_pass_the_stored_bytes_through() {
+        use crate::storage::{DocumentStoreExt, MemoryStore};
+
+        const POLICY: CachePolicy = CachePolicy {
+            max_age: 60,
+            vary: "Accept-Encoding",
+        };
+        let gzip = || {
+            http::Request::builder()
+                .header("accept-encoding", "br, gzip")
+                .body(())
+                .unwrap()
+        };
+        let store = MemoryStore::new();
+        store.put("people.json", &"stale").await.unwrap();
+        store
+            .put_compressed(&rendered_key("people.json"), &"rendered")
+            .await
+            .unwrap();
+        let stored = store.get_bytes(&rendered_key("people.json")).await.unwrap();
+
+        let response: http::Response<Vec<u8>> =
+            rendered_response(&store, &gzip(), "people.json", "application/json", POLICY)
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(response.headers()["Content-Encoding"], "gzip");
+        assert_eq!(response.body(), &stored);
+
+        let identity = http::Request::new(());
+        let response: Option<http::Response<Vec<u8>>> =
+            rendered_response(&store, &identity, "people.json", "application/json", POLICY)
+                .await
+                .unwrap();
+        assert!(response.is_none());
+
+        // A document written after its rendered copy is no longer described by it
+        store.put("people.json", &"fresh").await.unwrap();
+        let response: Option<http::Response<Vec<u8>>> =
+            rendered_response(&store, &gzip(), "people.json", "application/json", POLICY)
+                .await
+                .unwrap();
+        assert!(response.is_none());
+    }
+}
@@ -0,0 +1,156 @@
+//! `Content-Encoding` negotiation and compression of response bodies
+use flate2::write::GzEncoder;
+use std::io::{self, Write};
+
+/// Bodies shorter than this are sent as they are, compressing them saves
+/// less than the headers cost
+pub const MIN_COMPRESSED_LEN: usize = 1024;
+
+/// Brotli quality, 11 is the densest but far too slow to run per request
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    /// Best encoding allowed by an `Accept-Encoding` header, preferring
+    /// brotli over gzip when the client weighs them equally
+    pub fn negotiate(accept_encoding: Option<&str>) -> Encoding {
+        let Some(accept_encoding) = accept_encoding else {
+            return Encoding::Identity;
+        };
+
+        let (brotli, gzip) = qualities(accept_encoding);
+        if brotli > 0.0 && brotli >= gzip {
+            Encoding::Brotli
+        } else if gzip > 0.0 {
+            Encoding::Gzip
+        } else {
+            Encoding::Identity
+        }
+    }
+
+    /// Whether an `Accept-Encoding` header allows this encoding at all, even
+    /// when it prefers another
+    pub fn accepts(self, accept_encoding: Option<&str>) -> bool {
+        let (brotli, gzip) = accept_encoding.map_or((0.0, 0.0), qualities);
+        match self {
+            Encoding::Identity => true,
+            Encoding::Gzip => gzip > 0.0,
+            Encoding::Brotli => brotli > 0.0,
+        }
+    }
+
+    /// Encoding for a response body of `len` bytes, identity for small bodies
+    pub fn for_response(accept_encoding: Option<&str>, len: usize) -> Encoding {
+        if len < MIN_COMPRESSED_LEN {
+            Encoding::Identity
+        } else {
+            Encoding::negotiate(accept_encoding)
+        }
+    }
+
+    /// Value of the `Content-Encoding` header, none for identity
+    pub fn header_value(self) -> Option<&'static str> {
+        match self {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some("gzip"),
+            Encoding::Brotli => Some("br"),
+        }
+    }
+
+    pub fn encode(self, body: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Identity => Ok(body.to_vec()),
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            Encoding::Brotli => {
+                let mut out = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(
+                        &mut out,
+                        4096,
+                        BROTLI_QUALITY,
+                        BROTLI_WINDOW,
+                    );
+                    encoder.write_all(body)?;
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Quality values of brotli and gzip in an `Accept-Encoding` header, zero
+/// for codings it does not allow
+fn qualities(accept_encoding: &str) -> (f32, f32) {
+    let (mut brotli, mut gzip, mut any) = (None, None, None);
+    for coding in accept_encoding.split(',') {
+        let mut parts = coding.split(';');
+        let name = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        match name.as_str() {
+            "br" => brotli = Some(quality),
+            "gzip" | "x-gzip" => gzip = Some(quality),
+            "*" => any = Some(quality),
+            _ => {}
+        }
+    }
+
+    // `*` stands for every coding the header does not name itself
+    (brotli.or(any).unwrap_or(0.0), gzip.or(any).unwrap_or(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn negotiation_follows_quality_values() {
+        assert_eq!(Encoding::negotiate(None), Encoding::Identity);
+        assert_eq!(
+            Encoding::negotiate(Some("gzip, deflate, br")),
+            Encoding::Brotli
+        );
+        assert_eq!(Encoding::negotiate(Some("br;q=0.5, gzip")), Encoding::Gzip);
+        assert_eq!(Encoding::negotiate(Some("br;q=0, *")), Encoding::Gzip);
+        assert_eq!(Encoding::negotiate(Some("deflate")), Encoding::Identity);
+        assert!(Encoding::Gzip.accepts(Some("br, gzip;q=0.5")));
+        assert!(!Encoding::Gzip.accepts(Some("br, gzip;q=0")));
+        assert!(!Encoding::Gzip.accepts(None));
+        assert_eq!(
+            Encoding::for_response(Some("gzip"), MIN_COMPRESSED_LEN - 1),
+            Encoding::Identity
+        );
+    }
+
+    #[test]
+    fn bodies_round_trip() {
+        let body = br#"{"near_earth_objects":[]}"#.repeat(100);
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&Encoding::Gzip.encode(&body).unwrap()[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        let mut decoded = Vec::new();
+        brotli::Decompressor::new(&Encoding::Brotli.encode(&body).unwrap()[..], 4096)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+    }
+}
@@ -0,0 +1,396 @@
+//! Typed configuration every function loads from its environment at startup
+use chrono::NaiveDate;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Settings shared by the functions, each field read from the environment
+/// variable named in its doc comment and falling back to a default
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// `BUCKET_NAME`
+    pub bucket_name: String,
+    /// `LOCAL_STORAGE_DIR`, serve documents from this directory instead of the bucket
+    pub local_storage_dir: Option<String>,
+
+    /// `PEOPLE_FILE`
+    pub people_file: String,
+    /// `PEOPLE_EVENTS_FILE`, arrival and departure log kept next to the people document
+    pub people_events_file: String,
+    /// `LAUNCHES_FILE`
+    pub launches_file: String,
+    /// `LAUNCH_CHANGES_FILE`, schedule change log kept next to the launches document
+    pub launch_changes_file: String,
+    /// `NEO_FILE`
+    pub neo_file: String,
+
+    /// `NASA_API_KEY`, used instead of the SSM parameter when set
+    pub nasa_api_key: Option<String>,
+    /// `NASA_KEY_LOCATION`
+    pub nasa_key_location: String,
+    /// `LAUNCH_API_KEY`, used instead of the SSM parameter when set
+    pub launch_api_key: Option<String>,
+    /// `LAUNCH_KEY_LOCATION`
+    pub launch_key_location: String,
+
+    /// `NEO_FEED_URL`
+    pub neo_feed_url: String,
+    /// `PEOPLE_API_URL`
+    pub astros_url: String,
+    /// `LAUNCHES_API_URL`
+    pub launches_url: String,
+
+    /// `HTTP_MAX_ATTEMPTS`
+    pub http_max_attempts: u32,
+    /// `HTTP_TIMEOUT_MS`, per attempt
+    pub http_timeout: Duration,
+    /// `HTTP_BACKOFF_MS`
+    pub http_initial_backoff: Duration,
+    /// `HTTP_MAX_BACKOFF_MS`
+    pub http_max_backoff: Duration,
+
+    /// `DOCUMENT_CACHE_TTL_MS`, how long a parsed document is reused before
+    /// the store is asked whether it changed
+    pub document_cache_ttl: Duration,
+    /// `PEOPLE_MAX_CHANGE_RATIO`, largest accepted change in the number of
+    /// people in space; crew rotations can more than double it
+    pub people_max_change_ratio: f64,
+    /// `NEO_MAX_CHANGE_RATIO`, in objects per day, which swings widely
+    /// between quiet and busy days
+    pub neo_max_change_ratio: f64,
+    /// `LAUNCHES_MAX_CHANGE_RATIO`, in upcoming launches listed
+    pub launches_max_change_ratio: f64,
+    /// `SNAPSHOT_ACCEPT_AFTER_REJECTIONS`, quarantined runs of about the
+    /// same size in a row after which that size is accepted, 0 for never
+    pub snapshot_accept_after_rejections: usize,
+    /// `COMPRESS_DOCUMENTS`, store collected snapshots gzip-compressed
+    pub compress_documents: bool,
+
+    /// `NOTIFY_SUBSCRIPTIONS_FILE`, webhook subscriptions
+    pub subscriptions_file: String,
+    /// `NOTIFY_LEDGER_FILE`, notifications already delivered
+    pub notify_ledger_file: String,
+    /// `NEO_ALERT_LUNAR_DISTANCE`, hazardous approaches closer than this are notified
+    pub neo_alert_lunar_distance: f64,
+
+    /// `ENABLE_NEO_ARCHIVE`, write the per-day NEO archive next to the latest document
+    pub neo_archive_enabled: bool,
+    /// `START_DATE`, first day the NEO collector fetches when the event names none
+    pub neo_start_date: Option<NaiveDate>,
+    /// `END_DATE`, last day the NEO collector fetches when the event names none
+    pub neo_end_date: Option<NaiveDate>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bucket_name: "spaceclouddatabucket".to_string(),
+            local_storage_dir: None,
+            people_file: "people_in_space.json".to_string(),
+            people_events_file: "people_events.json".to_string(),
+            launches_file: "launches.json".to_string(),
+            launch_changes_file: "launch_changes.json".to_string(),
+            neo_file: "near_earth_objects.json".to_string(),
+            nasa_api_key: None,
+            nasa_key_location: "/space_cloud/keys/nasa_api_key".to_string(),
+            launch_api_key: None,
+            launch_key_location: "/space_cloud/keys/rocket_launch_live_api_key".to_string(),
+            neo_feed_url: "https://api.nasa.gov/neo/rest/v1/feed".to_string(),
+            astros_url: "http://api.open-notify.org/astros.json".to_string(),
+            launches_url: "https://fdo.rocketlaunch.live/json/launches".to_string(),
+            http_max_attempts: 4,
+            http_timeout: Duration::from_secs(10),
+            http_initial_backoff: Duration::from_millis(500),
+            http_max_backoff: Duration::from_secs(8),
+            document_cache_ttl: Duration::from_secs(60),
+            people_max_change_ratio: 3.0,
+            neo_max_change_ratio: 3.0,
+            launches_max_change_ratio: 0.5,
+            snapshot_accept_after_rejections: 3,
+            compress_documents: false,
+            subscriptions_file: "notify/subscriptions.json".to_string(),
+            notify_ledger_file: "notify/delivered.json".to_string(),
+            neo_alert_lunar_distance: 10.0,
+            neo_archive_enabled: true,
+            neo_start_date: None,
+            neo_end_date: None,
+        }
+    }
+}
+
+/// Every problem found while loading the configuration, reported together
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration: {}", self.problems.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Reads variables through `lookup`, collecting problems instead of stopping at the first
+struct Loader<F> {
+    lookup: F,
+    problems: Vec<String>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Loader<F> {
+    fn optional(&self, name: &str) -> Option<String> {
+        (self.lookup)(name).filter(|value| !value.trim().is_empty())
+    }
+
+    fn string(&self, name: &str, default: String) -> String {
+        self.optional(name).unwrap_or(default)
+    }
+
+    fn url(&mut self, name: &str, default: String) -> String {
+        let url = self.string(name, default);
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            self.problems
+                .push(format!("{} must be an http(s) URL, got {:?}", name, url));
+        }
+        url
+    }
+
+    fn parse<T: FromStr>(
+        &mut self,
+        name: &str,
+        default: T,
+        expected: &str,
+        valid: impl Fn(&T) -> bool,
+    ) -> T {
+        let Some(value) = self.optional(name) else {
+            return default;
+        };
+
+        match value.trim().parse::<T>() {
+            Ok(parsed) if valid(&parsed) => parsed,
+            _ => {
+                self.problems
+                    .push(format!("{} must be {}, got {:?}", name, expected, value));
+                default
+            }
+        }
+    }
+
+    fn millis(&mut self, name: &str, default: Duration) -> Duration {
+        let millis = self.parse(
+            name,
+            default.as_millis() as u64,
+            "a number of milliseconds",
+            |_| true,
+        );
+        Duration::from_millis(millis)
+    }
+
+    fn ratio(&mut self, name: &str, default: f64) -> f64 {
+        self.parse(name, default, "a non-negative number", |ratio: &f64| {
+            *ratio >= 0.0
+        })
+    }
+
+    fn date(&mut self, name: &str) -> Option<NaiveDate> {
+        let value = self.optional(name)?;
+
+        match NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+            Ok(date) => Some(date),
+            Err(_) => {
+                self.problems.push(format!(
+                    "{} must be a YYYY-MM-DD date, got {:?}",
+                    name, value
+                ));
+                None
+            }
+        }
+    }
+
+    fn flag(&mut self, name: &str, default: bool) -> bool {
+        let Some(value) = self.optional(name) else {
+            return default;
+        };
+
+        match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => true,
+            "0" | "false" | "no" | "off" => false,
+            _ => {
+                self.problems
+                    .push(format!("{} must be true or false, got {:?}", name, value));
+                default
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Load from the process environment
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Load from any variable source, e.g. a map in tests
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let defaults = Config::default();
+        let mut env = Loader {
+            lookup,
+            problems: Vec::new(),
+        };
+
+        let config = Config {
+            bucket_name: env.string("BUCKET_NAME", defaults.bucket_name),
+            local_storage_dir: env.optional("LOCAL_STORAGE_DIR"),
+            people_file: env.string("PEOPLE_FILE", defaults.people_file),
+            people_events_file: env.string("PEOPLE_EVENTS_FILE", defaults.people_events_file),
+            launches_file: env.string("LAUNCHES_FILE", defaults.launches_file),
+            launch_changes_file: env.string("LAUNCH_CHANGES_FILE", defaults.launch_changes_file),
+            neo_file: env.string("NEO_FILE", defaults.neo_file),
+            nasa_api_key: env.optional("NASA_API_KEY"),
+            nasa_key_location: env.string("NASA_KEY_LOCATION", defaults.nasa_key_location),
+            launch_api_key: env.optional("LAUNCH_API_KEY"),
+            launch_key_location: env.string("LAUNCH_KEY_LOCATION", defaults.launch_key_location),
+            neo_feed_url: env.url("NEO_FEED_URL", defaults.neo_feed_url),
+            astros_url: env.url("PEOPLE_API_URL", defaults.astros_url),
+            launches_url: env.url("LAUNCHES_API_URL", defaults.launches_url),
+            http_max_attempts: env.parse(
+                "HTTP_MAX_ATTEMPTS",
+                defaults.http_max_attempts,
+                "a positive integer",
+                |attempts| *attempts > 0,
+            ),
+            http_timeout: env.millis("HTTP_TIMEOUT_MS", defaults.http_timeout),
+            http_initial_backoff: env.millis("HTTP_BACKOFF_MS", defaults.http_initial_backoff),
+            http_max_backoff: env.millis("HTTP_MAX_BACKOFF_MS", defaults.http_max_backoff),
+            document_cache_ttl: env.millis("DOCUMENT_CACHE_TTL_MS", defaults.document_cache_ttl),
+            people_max_change_ratio: env
+                .ratio("PEOPLE_MAX_CHANGE_RATIO", defaults.people_max_change_ratio),
+            neo_max_change_ratio: env.ratio("NEO_MAX_CHANGE_RATIO", defaults.neo_max_change_ratio),
+            launches_max_change_ratio: env.ratio(
+                "LAUNCHES_MAX_CHANGE_RATIO",
+                defaults.launches_max_change_ratio,
+            ),
+            snapshot_accept_after_rejections: env.parse(
+                "SNAPSHOT_ACCEPT_AFTER_REJECTIONS",
+                defaults.snapshot_accept_after_rejections,
+                "a whole number",
+                |_| true,
+            ),
+            compress_documents: env.flag("COMPRESS_DOCUMENTS", defaults.compress_documents),
+            subscriptions_file: env
+                .string("NOTIFY_SUBSCRIPTIONS_FILE", defaults.subscriptions_file),
+            notify_ledger_file: env.string("NOTIFY_LEDGER_FILE", defaults.notify_ledger_file),
+            neo_alert_lunar_distance: env.parse(
+                "NEO_ALERT_LUNAR_DISTANCE",
+                defaults.neo_alert_lunar_distance,
+                "a positive number",
+                |distance: &f64| *distance > 0.0,
+            ),
+            neo_archive_enabled: env.flag("ENABLE_NEO_ARCHIVE", defaults.neo_archive_enabled),
+            neo_start_date: env.date("START_DATE"),
+            neo_end_date: env.date("END_DATE"),
+        };
+
+        if config.http_max_backoff < config.http_initial_backoff {
+            env.problems.push(format!(
+                "HTTP_MAX_BACKOFF_MS ({}) must not be below HTTP_BACKOFF_MS ({})",
+                config.http_max_backoff.as_millis(),
+                config.http_initial_backoff.as_millis()
+            ));
+        }
+
+        if env.problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError {
+                problems: env.problems,
+            })
+        }
+    }
+
+    #[cfg(feature = "fetch")]
+    pub fn retry_policy(&self) -> crate::fetch::RetryPolicy {
+        crate::fetch::RetryPolicy {
+            max_attempts: self.http_max_attempts,
+            initial_backoff: self.http_initial_backoff,
+            max_backoff: self.http_max_backoff,
+            attempt_timeout: self.http_timeout,
+        }
+    }
+
+    /// Policy for a collector's snapshots, allowing `max_change_ratio`, one
+    /// of the per-document ratios above
+    pub fn snapshot_policy(&self, max_change_ratio: f64) -> crate::validation::SnapshotPolicy {
+        crate::validation::SnapshotPolicy {
+            max_change_ratio,
+            compressed: self.compress_documents,
+            accept_after_rejections: self.snapshot_accept_after_rejections,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn empty_environment_uses_defaults() {
+        assert_eq!(load(&[]).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn variables_override_defaults() {
+        let config = load(&[
+            ("BUCKET_NAME", "test-bucket"),
+            ("NEO_FEED_URL", "http://127.0.0.1:9000/feed"),
+            ("HTTP_TIMEOUT_MS", "2500"),
+            ("ENABLE_NEO_ARCHIVE", "false"),
+            ("COMPRESS_DOCUMENTS", "true"),
+            ("SNAPSHOT_ACCEPT_AFTER_REJECTIONS", "0"),
+            ("NASA_API_KEY", ""),
+            ("START_DATE", "2023-11-01"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.bucket_name, "test-bucket");
+        assert_eq!(config.neo_feed_url, "http://127.0.0.1:9000/feed");
+        assert_eq!(config.http_timeout, Duration::from_millis(2500));
+        assert!(!config.neo_archive_enabled);
+        let policy = config.snapshot_policy(config.neo_max_change_ratio);
+        assert!(policy.compressed);
+        assert_eq!(policy.accept_after_rejections, 0);
+        assert_eq!(config.nasa_api_key, None);
+        assert_eq!(config.neo_start_date, NaiveDate::from_ymd_opt(2023, 11, 1));
+        assert_eq!(config.neo_end_date, None);
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let err = load(&[
+            ("HTTP_MAX_ATTEMPTS", "0"),
+            ("PEOPLE_API_URL", "api.open-notify.org"),
+            ("HTTP_BACKOFF_MS", "9000"),
+            ("END_DATE", "tomorrow"),
+        ])
+        .unwrap_err();
+
+        assert_eq!(err.problems.len(), 4);
+        assert_eq!(
+            err.to_string(),
+            "Invalid configuration: PEOPLE_API_URL must be an http(s) URL, got \"api.open-notify.org\"; \
+             HTTP_MAX_ATTEMPTS must be a positive integer, got \"0\"; \
+             END_DATE must be a YYYY-MM-DD date, got \"tomorrow\"; \
+             HTTP_MAX_BACKOFF_MS (8000) must not be below HTTP_BACKOFF_MS (9000)"
+        );
+    }
+}
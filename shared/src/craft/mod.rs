@@ -0,0 +1,184 @@
+//! Registry of the spacecraft people are reported aboard
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CraftKind {
+    /// A crewed orbital station
+    Station,
+    /// A crew vehicle, typically docked to a station
+    Vehicle,
+}
+
+/// What we know about a craft beyond its name
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct CraftInfo {
+    /// Left out when serialized, the group or person it describes already
+    /// carries the name
+    #[serde(skip)]
+    pub name: &'static str,
+    #[serde(skip)]
+    pub aliases: &'static [&'static str],
+    pub operator: &'static str,
+    pub kind: CraftKind,
+    /// Launch date of the craft, or of its first module for a station
+    pub launched: &'static str,
+}
+
+pub const KNOWN_CRAFTS: &[CraftInfo] = &[
+    CraftInfo {
+        name: "ISS",
+        aliases: &["International Space Station"],
+        operator: "NASA, Roscosmos, ESA, JAXA, CSA",
+        kind: CraftKind::Station,
+        launched: "1998-11-20",
+    },
+    CraftInfo {
+        name: "Tiangong",
+        aliases: &["CSS", "Tiangong Space Station", "Chinese Space Station"],
+        operator: "CMSA",
+        kind: CraftKind::Station,
+        launched: "2021-04-29",
+    },
+    CraftInfo {
+        name: "Shenzhou 15",
+        aliases: &["Shenzhou-15"],
+        operator: "CMSA",
+        kind: CraftKind::Vehicle,
+        launched: "2022-11-29",
+    },
+    CraftInfo {
+        name: "Shenzhou 16",
+        aliases: &["Shenzhou-16"],
+        operator: "CMSA",
+        kind: CraftKind::Vehicle,
+        launched: "2023-05-30",
+    },
+    CraftInfo {
+        name: "Shenzhou 17",
+        aliases: &["Shenzhou-17"],
+        operator: "CMSA",
+        kind: CraftKind::Vehicle,
+        launched: "2023-10-26",
+    },
+    CraftInfo {
+        name: "Shenzhou 18",
+        aliases: &["Shenzhou-18"],
+        operator: "CMSA",
+        kind: CraftKind::Vehicle,
+        launched: "2024-04-25",
+    },
+    CraftInfo {
+        name: "Soyuz MS-24",
+        aliases: &[],
+        operator: "Roscosmos",
+        kind: CraftKind::Vehicle,
+        launched: "2023-09-15",
+    },
+    CraftInfo {
+        name: "Soyuz MS-25",
+        aliases: &[],
+        operator: "Roscosmos",
+        kind: CraftKind::Vehicle,
+        launched: "2024-03-23",
+    },
+];
+
+/// A craft name as reported upstream, resolved against [`KNOWN_CRAFTS`]
+///
+/// Unknown names are kept as [`Craft::Other`] so a new crew never breaks
+/// deserialization; it serializes back to the plain name either way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Craft {
+    Known(&'static CraftInfo),
+    Other(String),
+}
+
+impl Craft {
+    /// Resolve a name case-insensitively, also matching known aliases
+    pub fn from_name(name: &str) -> Self {
+        let name = name.trim();
+        KNOWN_CRAFTS
+            .iter()
+            .find(|craft| {
+                craft.name.eq_ignore_ascii_case(name)
+                    || craft
+                        .aliases
+                        .iter()
+                        .any(|alias| alias.eq_ignore_ascii_case(name))
+            })
+            .map(Craft::Known)
+            .unwrap_or_else(|| Craft::Other(name.to_string()))
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Craft::Known(info) => info.name,
+            Craft::Other(name) => name,
+        }
+    }
+
+    pub fn info(&self) -> Option<&'static CraftInfo> {
+        match self {
+            Craft::Known(info) => Some(info),
+            Craft::Other(_) => None,
+        }
+    }
+}
+
+impl Serialize for Craft {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl schemars::JsonSchema for Craft {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Craft".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "description": "Name of the craft, e.g. ISS or Tiangong"
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Craft {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Craft::from_name(&name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_names_and_aliases_resolve() {
+        assert_eq!(Craft::from_name("ISS").name(), "ISS");
+        assert_eq!(Craft::from_name("iss").name(), "ISS");
+        assert_eq!(Craft::from_name("Shenzhou-17").name(), "Shenzhou 17");
+        assert_eq!(
+            Craft::from_name("Tiangong").info().map(|info| info.kind),
+            Some(CraftKind::Station)
+        );
+    }
+
+    #[test]
+    fn unknown_craft_round_trips_as_other() {
+        let craft: Craft = serde_json::from_str("\"Axiom Mission 4\"").unwrap();
+
+        assert_eq!(craft, Craft::Other("Axiom Mission 4".to_string()));
+        assert_eq!(craft.info(), None);
+        assert_eq!(
+            serde_json::to_string(&craft).unwrap(),
+            "\"Axiom Mission 4\""
+        );
+    }
+}
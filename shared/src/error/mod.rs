@@ -0,0 +1,151 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Content type for RFC 7807 problem documents
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Failures a read endpoint reports to its caller instead of crashing the lambda
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request itself is invalid, e.g. a malformed query parameter
+    BadRequest(String),
+    /// The requested document does not exist
+    NotFound(String),
+    /// The backing store or an upstream API could not be reached
+    UpstreamUnavailable(String),
+    /// A stored document exists but could not be parsed
+    CorruptDocument(String),
+    /// The function's environment is missing required configuration
+    Misconfigured(String),
+}
+
+/// Body of an `application/problem+json` response
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: &'static str,
+    pub title: &'static str,
+    pub status: u16,
+    pub detail: String,
+}
+
+impl ApiError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::BadRequest(_) => 400,
+            ApiError::NotFound(_) => 404,
+            ApiError::UpstreamUnavailable(_) => 503,
+            ApiError::CorruptDocument(_) | ApiError::Misconfigured(_) => 500,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "Bad Request",
+            ApiError::NotFound(_) => "Not Found",
+            ApiError::UpstreamUnavailable(_) => "Service Unavailable",
+            ApiError::CorruptDocument(_) => "Corrupt Document",
+            ApiError::Misconfigured(_) => "Misconfigured",
+        }
+    }
+
+    pub fn detail(&self) -> &str {
+        match self {
+            ApiError::BadRequest(detail)
+            | ApiError::NotFound(detail)
+            | ApiError::UpstreamUnavailable(detail)
+            | ApiError::CorruptDocument(detail)
+            | ApiError::Misconfigured(detail) => detail,
+        }
+    }
+
+    pub fn problem(&self) -> ProblemDetails {
+        ProblemDetails {
+            problem_type: "about:blank",
+            title: self.title(),
+            status: self.status(),
+            detail: self.detail().to_string(),
+        }
+    }
+
+    pub fn to_problem_json(&self) -> String {
+        serde_json::to_string(&self.problem()).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.title(), self.detail())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(feature = "storage")]
+impl From<crate::storage::StorageError> for ApiError {
+    fn from(err: crate::storage::StorageError) -> Self {
+        use crate::storage::StorageError;
+
+        match err {
+            // The storage key is an implementation detail, so it only goes to the logs
+            StorageError::NotFound(_) => {
+                log::warn!("{}", err);
+                ApiError::NotFound("The requested data is not available".to_string())
+            }
+            StorageError::Serialization(_) => ApiError::CorruptDocument(err.to_string()),
+            StorageError::Backend(_) => ApiError::UpstreamUnavailable(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variants_map_to_status_codes() {
+        assert_eq!(ApiError::BadRequest(String::new()).status(), 400);
+        assert_eq!(ApiError::NotFound(String::new()).status(), 404);
+        assert_eq!(ApiError::UpstreamUnavailable(String::new()).status(), 503);
+        assert_eq!(ApiError::CorruptDocument(String::new()).status(), 500);
+        assert_eq!(ApiError::Misconfigured(String::new()).status(), 500);
+    }
+
+    #[test]
+    fn problem_json_has_rfc7807_fields() {
+        let body: serde_json::Value = serde_json::from_str(
+            &ApiError::NotFound("No launch with id 42".to_string()).to_problem_json(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "No launch with id 42"
+            })
+        );
+    }
+
+    #[cfg(feature = "storage")]
+    #[test]
+    fn storage_errors_are_classified() {
+        use crate::storage::StorageError;
+
+        let corrupt = serde_json::from_str::<u32>("{").unwrap_err();
+        let missing = ApiError::from(StorageError::NotFound("launches.json".to_string()));
+        assert_eq!(missing.status(), 404);
+        assert!(!missing.detail().contains("launches.json"));
+        assert_eq!(
+            ApiError::from(StorageError::Serialization(corrupt)).status(),
+            500
+        );
+        assert_eq!(
+            ApiError::from(StorageError::Backend("timeout".into())).status(),
+            503
+        );
+    }
+}
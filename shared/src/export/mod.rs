@@ -0,0 +1,275 @@
+//! Flat CSV and NDJSON renderings of the stored NEO and launch documents,
+//! one row per close approach or per launch
+use crate::persistencemodels::{NearEarthObject, UpcomingLaunchesLaunch};
+use serde::Serialize;
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Representation a read endpoint responds with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    Ndjson,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Format> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "ndjson" | "jsonl" => Some(Format::Ndjson),
+            _ => None,
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type.trim().to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            "text/csv" | "text/*" => Some(Format::Csv),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => {
+                Some(Format::Ndjson)
+            }
+            _ => None,
+        }
+    }
+
+    /// The format asked for by `?format=`, or else the most preferred one
+    /// `Accept` lists; JSON when neither names a supported format
+    pub fn negotiate(format: Option<&str>, accept: Option<&str>) -> Result<Format, String> {
+        if let Some(format) = format {
+            return Format::from_name(format).ok_or_else(|| {
+                format!("format must be one of json, csv or ndjson, got {}", format)
+            });
+        }
+
+        let mut ranges: Vec<(f32, Format)> = accept
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let format = Format::from_media_type(parts.next()?)?;
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|quality| quality.parse().ok())
+                    .unwrap_or(1.0);
+                Some((quality, format))
+            })
+            .filter(|(quality, _)| *quality > 0.0)
+            .collect();
+        // Stable, so equally preferred ranges keep the order they were listed in
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        Ok(ranges.first().map_or(Format::Json, |(_, format)| *format))
+    }
+
+    /// [`negotiate`](Self::negotiate) for a request, given the value of its
+    /// `format` query parameter
+    pub fn from_request<R>(
+        request: &http::Request<R>,
+        format: Option<&str>,
+    ) -> Result<Format, String> {
+        let accept = request
+            .headers()
+            .get("accept")
+            .and_then(|value| value.to_str().ok());
+        Format::negotiate(format, accept)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => CSV_CONTENT_TYPE,
+            Format::Ndjson => NDJSON_CONTENT_TYPE,
+        }
+    }
+}
+
+/// One close approach of a near earth object
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CloseApproachRow<'a> {
+    pub id: &'a str,
+    pub name: &'a str,
+    pub close_approach_date: &'a str,
+    pub orbiting_body: &'a str,
+    pub miss_distance_km: f64,
+    pub miss_distance_lunar: f64,
+    pub relative_velocity_km_s: f64,
+    pub estimated_diameter_min_m: f64,
+    pub estimated_diameter_max_m: f64,
+    pub is_potentially_hazardous_asteroid: bool,
+    pub is_sentry_object: bool,
+}
+
+pub fn close_approach_rows(neos: &[NearEarthObject]) -> Vec<CloseApproachRow<'_>> {
+    neos.iter()
+        .flat_map(|neo| {
+            neo.close_approach_data
+                .iter()
+                .map(move |approach| CloseApproachRow {
+                    id: &neo.id,
+                    name: &neo.name,
+                    close_approach_date: &approach.close_approach_date,
+                    orbiting_body: &approach.orbiting_body,
+                    miss_distance_km: approach.miss_distance.kilometers,
+                    miss_distance_lunar: approach.miss_distance.lunar,
+                    relative_velocity_km_s: approach.relative_velocity.kilometers_per_second,
+                    estimated_diameter_min_m: neo.estimated_diameter.meters.estimated_diameter_min,
+                    estimated_diameter_max_m: neo.estimated_diameter.meters.estimated_diameter_max,
+                    is_potentially_hazardous_asteroid: neo.is_potentially_hazardous_asteroid,
+                    is_sentry_object: neo.is_sentry_object,
+                })
+        })
+        .collect()
+}
+
+/// One launch, with its pad and tags flattened into columns
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LaunchRow<'a> {
+    pub id: u64,
+    pub name: &'a str,
+    pub provider: &'a str,
+    pub vehicle: &'a str,
+    pub pad: Option<&'a str>,
+    pub location: Option<&'a str>,
+    pub state: Option<&'a str>,
+    pub country: Option<&'a str>,
+    /// `t0`, or the window opening while there is none
+    pub net: Option<&'a str>,
+    pub win_open: Option<&'a str>,
+    pub win_close: Option<&'a str>,
+    pub suborbital: bool,
+    /// Tags separated by `;`
+    pub tags: String,
+    pub mission_description: &'a str,
+    pub modified: &'a str,
+}
+
+pub fn launch_rows(launches: &[UpcomingLaunchesLaunch]) -> Vec<LaunchRow<'_>> {
+    launches
+        .iter()
+        .map(|launch| {
+            let pad = launch.pad.as_ref();
+            LaunchRow {
+                id: launch.id,
+                name: &launch.name,
+                provider: &launch.provider.name,
+                vehicle: &launch.vehicle.name,
+                pad: pad.map(|pad| pad.name.as_str()),
+                location: pad.map(|pad| pad.location.name.as_str()),
+                state: pad.map(|pad| pad.location.state_name.as_str()),
+                country: pad.map(|pad| pad.location.country.as_str()),
+                net: launch.t0.as_deref().or(launch.win_open.as_deref()),
+                win_open: launch.win_open.as_deref(),
+                win_close: launch.win_close.as_deref(),
+                suborbital: launch.suborbital,
+                tags: launch
+                    .tags
+                    .iter()
+                    .map(|tag| tag.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                mission_description: &launch.mission_description,
+                modified: &launch.modified,
+            }
+        })
+        .collect()
+}
+
+/// CSV with a header row taken from the field names; empty without rows
+pub fn to_csv<T: Serialize>(rows: &[T]) -> Result<String, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|err| csv::Error::from(err.into_error()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// One JSON document per line
+pub fn to_ndjson<T: Serialize>(rows: &[T]) -> Result<String, serde_json::Error> {
+    let mut out = String::new();
+    for row in rows {
+        out.push_str(&serde_json::to_string(row)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_parameter_wins_over_accept() {
+        assert_eq!(
+            Format::negotiate(Some("csv"), Some("application/json")),
+            Ok(Format::Csv)
+        );
+        assert!(Format::negotiate(Some("xml"), None).is_err());
+    }
+
+    #[test]
+    fn accept_picks_the_most_preferred_supported_type() {
+        assert_eq!(Format::negotiate(None, None), Ok(Format::Json));
+        assert_eq!(Format::negotiate(None, Some("text/csv")), Ok(Format::Csv));
+        assert_eq!(
+            Format::negotiate(None, Some("text/html, application/x-ndjson")),
+            Ok(Format::Ndjson)
+        );
+        assert_eq!(
+            Format::negotiate(None, Some("application/json;q=0.5, text/csv;q=0.9")),
+            Ok(Format::Csv)
+        );
+        assert_eq!(
+            Format::negotiate(None, Some("text/csv;q=0, */*")),
+            Ok(Format::Json)
+        );
+        assert_eq!(Format::negotiate(None, Some("image/png")), Ok(Format::Json));
+    }
+
+    #[test]
+    fn requests_are_negotiated_from_their_accept_header() {
+        let request = http::Request::builder()
+            .header("accept", "text/csv")
+            .body(())
+            .unwrap();
+        assert_eq!(Format::from_request(&request, None), Ok(Format::Csv));
+        assert_eq!(
+            Format::from_request(&request, Some("ndjson")),
+            Ok(Format::Ndjson)
+        );
+    }
+
+    #[derive(Serialize)]
+    struct Row<'a> {
+        name: &'a str,
+        pad: Option<&'a str>,
+    }
+
+    #[test]
+    fn csv_quotes_fields_and_leaves_missing_values_empty() {
+        let rows = [
+            Row {
+                name: "Starlink 6-26",
+                pad: Some("SLC-40, Cape Canaveral"),
+            },
+            Row {
+                name: "Say \"hi\"",
+                pad: None,
+            },
+        ];
+
+        assert_eq!(
+            to_csv(&rows).unwrap(),
+            "name,pad\nStarlink 6-26,\"SLC-40, Cape Canaveral\"\n\"Say \"\"hi\"\"\",\n"
+        );
+        assert_eq!(
+            to_ndjson(&rows[..1]).unwrap(),
+            "{\"name\":\"Starlink 6-26\",\"pad\":\"SLC-40, Cape Canaveral\"}\n"
+        );
+    }
+}
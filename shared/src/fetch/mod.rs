@@ -0,0 +1,335 @@
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+/// How an upstream fetch is retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one
+    pub max_attempts: u32,
+    /// Upper bound of the jittered delay before the first retry, doubled on each retry
+    pub initial_backoff: Duration,
+    /// Cap for both the backoff and any `Retry-After` the upstream asks for
+    pub max_backoff: Duration,
+    /// Timeout applied to each individual attempt
+    pub attempt_timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+            attempt_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Full-jitter exponential backoff before retry number `retry` (starting at 1)
+    fn backoff(&self, retry: u32) -> Duration {
+        let ceiling = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+#[derive(Debug)]
+pub enum FetchError {
+    /// The upstream answered with a non-success status
+    Status { url: String, status: u16 },
+    /// The request could not be sent or timed out
+    Transport { url: String, source: reqwest::Error },
+    /// The body was not the expected JSON document
+    Decode {
+        url: String,
+        source: serde_json::Error,
+    },
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Status { url, status } => write!(f, "{} returned status {}", url, status),
+            FetchError::Transport { url, source } => {
+                write!(f, "Request to {} failed: {}", url, source)
+            }
+            FetchError::Decode { url, source } => {
+                write!(f, "Unexpected response from {}: {}", url, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Status { .. } => None,
+            FetchError::Transport { source, .. } => Some(source),
+            FetchError::Decode { source, .. } => Some(source),
+        }
+    }
+}
+
+/// The URL without its query string, so API keys never end up in logs or errors
+fn redact(url: &str) -> String {
+    url.split('?').next().unwrap_or_default().to_string()
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Delay requested through `Retry-After`, either in seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .ok(),
+    }
+}
+
+/// Sends requests upstream, retrying transient failures
+#[derive(Debug, Clone, Default)]
+pub struct Fetcher {
+    client: reqwest::Client,
+    policy: RetryPolicy,
+}
+
+impl Fetcher {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self::with_client(reqwest::Client::new(), policy)
+    }
+
+    pub fn with_client(client: reqwest::Client, policy: RetryPolicy) -> Self {
+        Fetcher { client, policy }
+    }
+
+    /// Send the request built by `request` and return the response body,
+    /// retrying connection errors, timeouts, 429 and 5xx responses according
+    /// to the policy
+    async fn send(
+        &self,
+        url: &str,
+        request: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    ) -> Result<String, FetchError> {
+        let redacted = redact(url);
+        let mut attempt = 1;
+
+        loop {
+            let result = request(&self.client)
+                .timeout(self.policy.attempt_timeout)
+                .send()
+                .await;
+
+            let (error, delay) = match result {
+                Ok(response) if response.status().is_success() => {
+                    return response
+                        .text()
+                        .await
+                        .map_err(|source| FetchError::Transport {
+                            url: redacted,
+                            source: source.without_url(),
+                        });
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let error = FetchError::Status {
+                        url: redacted.clone(),
+                        status: status.as_u16(),
+                    };
+                    if !is_retryable(status) {
+                        return Err(error);
+                    }
+                    (error, retry_after(response.headers()))
+                }
+                // reqwest prints the full URL, query string included
+                Err(source) => (
+                    FetchError::Transport {
+                        url: redacted.clone(),
+                        source: source.without_url(),
+                    },
+                    None,
+                ),
+            };
+
+            if attempt >= self.policy.max_attempts {
+                return Err(error);
+            }
+
+            let delay = delay
+                .map(|delay| delay.min(self.policy.max_backoff))
+                .unwrap_or_else(|| self.policy.backoff(attempt));
+            log::warn!(
+                "Attempt {} of {} failed ({}), retrying in {:?}",
+                attempt,
+                self.policy.max_attempts,
+                error,
+                delay
+            );
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Fetch `url` and return the body
+    pub async fn get_text(&self, url: &str) -> Result<String, FetchError> {
+        self.send(url, |client| client.get(url)).await
+    }
+
+    /// POST a JSON `body` with extra `headers` to `url` and return the response body
+    pub async fn post_json(
+        &self,
+        url: &str,
+        body: &str,
+        headers: &[(&str, String)],
+    ) -> Result<String, FetchError> {
+        self.send(url, |client| {
+            headers.iter().fold(
+                client
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.to_string()),
+                |request, (name, value)| request.header(*name, value),
+            )
+        })
+        .await
+    }
+
+    /// Fetch `url` and deserialize the JSON body
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, FetchError> {
+        let body = self.get_text(url).await?;
+        serde_json::from_str(&body).map_err(|source| FetchError::Decode {
+            url: redact(url),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn quick_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            attempt_timeout: Duration::from_secs(2),
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    mod retries {
+        use super::*;
+        use crate::testing::{StubResponse, StubServer};
+
+        #[derive(serde::Deserialize)]
+        struct Count {
+            count: u32,
+        }
+
+        #[tokio::test]
+        async fn retries_server_errors_and_throttling_until_success() {
+            let server = StubServer::builder()
+                .respond("/feed", StubResponse::status(503))
+                .respond(
+                    "/feed",
+                    StubResponse::status(429).with_header("retry-after", "0"),
+                )
+                .respond("/feed", StubResponse::json(r#"{"count":3}"#))
+                .start()
+                .await;
+
+            let body: Count = Fetcher::new(quick_policy(4))
+                .get_json(&server.url("/feed?api_key=secret"))
+                .await
+                .unwrap();
+
+            assert_eq!(body.count, 3);
+            assert_eq!(server.requests().len(), 3);
+        }
+
+        #[tokio::test]
+        async fn gives_up_after_max_attempts() {
+            let server = StubServer::builder()
+                .respond("/feed", StubResponse::status(503))
+                .start()
+                .await;
+
+            let err = Fetcher::new(quick_policy(2))
+                .get_text(&server.url("/feed?api_key=secret"))
+                .await
+                .unwrap_err();
+
+            assert!(matches!(err, FetchError::Status { status: 503, .. }));
+            assert!(!err.to_string().contains("secret"));
+            assert_eq!(server.requests().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn client_errors_are_not_retried() {
+            let server = StubServer::builder().start().await;
+
+            let err = Fetcher::new(quick_policy(4))
+                .get_text(&server.url("/missing"))
+                .await
+                .unwrap_err();
+
+            assert!(matches!(err, FetchError::Status { status: 404, .. }));
+            assert_eq!(server.requests().len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn transport_errors_leave_out_the_query_string() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let err = Fetcher::new(quick_policy(1))
+            .get_text(&format!("http://127.0.0.1:{}/feed?api_key=secret", port))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, FetchError::Transport { .. }));
+        assert!(!err.to_string().contains("secret"), "{}", err);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = quick_policy(10);
+        for retry in 1..10 {
+            assert!(policy.backoff(retry) <= policy.max_backoff);
+        }
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        let later = SystemTime::now() + Duration::from_secs(120);
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_str(&httpdate::fmt_http_date(later)).unwrap(),
+        );
+        let delay = retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(100) && delay <= Duration::from_secs(120));
+    }
+}
@@ -0,0 +1,259 @@
+//! Schedule changes derived from successive upcoming launch snapshots
+use crate::persistencemodels::{UpcomingLaunches, UpcomingLaunchesLaunch};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LaunchChangeKind {
+    /// The launch appeared in the listing
+    Added,
+    /// The launch dropped off the listing, because it flew or was cancelled
+    Removed,
+    /// The NET moved
+    Slipped,
+    /// The NET moved after it had already arrived
+    Scrubbed,
+    /// The window opened or closed at a different time with the NET unchanged
+    WindowChanged,
+    /// Other details such as the description or pad changed, which the
+    /// listing marks by moving the launch's `modified` time
+    Updated,
+}
+
+/// The schedule fields of a launch that changes are tracked for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct LaunchSchedule {
+    /// No-earlier-than time, `t0` or else the window opening
+    pub net: Option<String>,
+    pub win_open: Option<String>,
+    pub win_close: Option<String>,
+    pub sort_date: String,
+}
+
+impl LaunchSchedule {
+    fn of(launch: &UpcomingLaunchesLaunch) -> Self {
+        LaunchSchedule {
+            net: launch.t0.clone().or_else(|| launch.win_open.clone()),
+            win_open: launch.win_open.clone(),
+            win_close: launch.win_close.clone(),
+            sort_date: launch.sort_date.clone(),
+        }
+    }
+
+    fn net_time(&self) -> Option<DateTime<Utc>> {
+        let net = self.net.as_deref()?;
+        DateTime::parse_from_rfc3339(net)
+            .ok()
+            .map(|net| net.with_timezone(&Utc))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct LaunchChange {
+    pub id: u64,
+    pub name: String,
+    pub kind: LaunchChangeKind,
+    /// Collection time (RFC 3339) of the snapshot the change was first seen in
+    pub detected_at: String,
+    /// Schedule before the change, absent for new launches
+    pub from: Option<LaunchSchedule>,
+    /// Schedule after the change, absent for removed launches
+    pub to: Option<LaunchSchedule>,
+}
+
+/// Body of the `/launches/{id}/history` response
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct LaunchHistoryResponse {
+    pub id: u64,
+    pub changes: Vec<LaunchChange>,
+}
+
+/// Append-only log of launch changes, oldest first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LaunchChangeLog {
+    pub changes: Vec<LaunchChange>,
+}
+
+impl LaunchChangeLog {
+    pub fn for_launch(&self, id: u64) -> Vec<LaunchChange> {
+        self.changes
+            .iter()
+            .filter(|change| change.id == id)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Changes turning `previous` into `current`, detected at `now`; with no
+/// previous snapshot every launch counts as added
+pub fn diff_launches(
+    previous: Option<&UpcomingLaunches>,
+    current: &UpcomingLaunches,
+    now: DateTime<Utc>,
+) -> Vec<LaunchChange> {
+    let detected_at = now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let change = |launch: &UpcomingLaunchesLaunch, kind, from, to| LaunchChange {
+        id: launch.id,
+        name: launch.name.clone(),
+        kind,
+        detected_at: detected_at.clone(),
+        from,
+        to,
+    };
+    let previous_launches = previous.map_or(&[][..], |previous| &previous.result[..]);
+
+    let mut changes = Vec::new();
+    for before in previous_launches {
+        if !current.result.iter().any(|after| after.id == before.id) {
+            let from = LaunchSchedule::of(before);
+            changes.push(change(before, LaunchChangeKind::Removed, Some(from), None));
+        }
+    }
+
+    for after in &current.result {
+        let to = LaunchSchedule::of(after);
+        let Some(before) = previous_launches
+            .iter()
+            .find(|before| before.id == after.id)
+        else {
+            changes.push(change(after, LaunchChangeKind::Added, None, Some(to)));
+            continue;
+        };
+        let from = LaunchSchedule::of(before);
+
+        let kind = if from.net != to.net || (from.net.is_none() && from.sort_date != to.sort_date) {
+            let arrived = from.net_time().is_some_and(|net| net <= now);
+            let later = match (from.net_time(), to.net_time()) {
+                (Some(from), Some(to)) => to > from,
+                _ => true,
+            };
+            if arrived && later {
+                LaunchChangeKind::Scrubbed
+            } else {
+                LaunchChangeKind::Slipped
+            }
+        } else if from.win_open != to.win_open || from.win_close != to.win_close {
+            LaunchChangeKind::WindowChanged
+        } else if before.modified != after.modified {
+            LaunchChangeKind::Updated
+        } else {
+            continue;
+        };
+        changes.push(change(after, kind, Some(from), Some(to)));
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn launch(id: u64, t0: Option<&str>, win_close: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "cospar_id": null,
+            "sort_date": "1699000000",
+            "name": format!("Launch {}", id),
+            "provider": { "id": 1, "name": "SpaceX", "slug": "spacex" },
+            "vehicle": { "id": 1, "name": "Falcon 9", "company_id": 1, "slug": "falcon-9", "pad": null },
+            "pad": null,
+            "missions": [],
+            "mission_description": "",
+            "launch_description": "",
+            "win_open": t0,
+            "t0": t0,
+            "win_close": win_close,
+            "date_str": "Nov 03",
+            "tags": [],
+            "slug": format!("launch-{}", id),
+            "weather_summary": null,
+            "weather_temp": 0,
+            "weather_condition": null,
+            "weather_wind_mph": null,
+            "weather_icon": null,
+            "weather_updated": null,
+            "quick_text": null,
+            "suborbital": false,
+            "modified": "2023-11-01T00:00:00+00:00"
+        })
+    }
+
+    fn snapshot(launches: Vec<serde_json::Value>) -> UpcomingLaunches {
+        serde_json::from_value(serde_json::json!({
+            "valid_auth": true,
+            "count": launches.len(),
+            "limit": 25,
+            "total": launches.len(),
+            "last_page": 1,
+            "result": launches,
+        }))
+        .unwrap()
+    }
+
+    fn kinds(changes: &[LaunchChange]) -> Vec<(u64, LaunchChangeKind)> {
+        changes
+            .iter()
+            .map(|change| (change.id, change.kind))
+            .collect()
+    }
+
+    #[test]
+    fn diff_classifies_schedule_changes() {
+        let previous = snapshot(vec![
+            launch(1, Some("2023-11-03T08:45:00Z"), None),
+            launch(2, Some("2023-11-01T12:00:00Z"), None),
+            launch(
+                3,
+                Some("2023-11-05T10:00:00Z"),
+                Some("2023-11-05T12:00:00Z"),
+            ),
+            launch(4, Some("2023-11-06T10:00:00Z"), None),
+            launch(5, None, None),
+            launch(7, Some("2023-11-08T10:00:00Z"), None),
+        ]);
+        let mut described = launch(7, Some("2023-11-08T10:00:00Z"), None);
+        described["launch_description"] = "Now with a payload".into();
+        described["modified"] = "2023-11-01T12:00:00+00:00".into();
+        let current = snapshot(vec![
+            launch(1, Some("2023-11-04T08:45:00Z"), None),
+            launch(2, Some("2023-11-02T12:00:00Z"), None),
+            launch(
+                3,
+                Some("2023-11-05T10:00:00Z"),
+                Some("2023-11-05T14:00:00Z"),
+            ),
+            launch(5, None, None),
+            launch(6, None, None),
+            described,
+        ]);
+        let now = DateTime::parse_from_rfc3339("2023-11-01T18:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let changes = diff_launches(Some(&previous), &current, now);
+
+        assert_eq!(
+            kinds(&changes),
+            vec![
+                (4, LaunchChangeKind::Removed),
+                (1, LaunchChangeKind::Slipped),
+                (2, LaunchChangeKind::Scrubbed),
+                (3, LaunchChangeKind::WindowChanged),
+                (6, LaunchChangeKind::Added),
+                (7, LaunchChangeKind::Updated),
+            ]
+        );
+        assert_eq!(changes[1].detected_at, "2023-11-01T18:00:00Z");
+        assert_eq!(
+            changes[1].to.as_ref().unwrap().net.as_deref(),
+            Some("2023-11-04T08:45:00Z")
+        );
+        assert_eq!(diff_launches(None, &current, now).len(), 6);
+    }
+}
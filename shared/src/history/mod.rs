@@ -0,0 +1,10 @@
+//! Event logs built by diffing each collected snapshot against the previous one
+#[cfg(feature = "apimodels")]
+mod people;
+#[cfg(feature = "apimodels")]
+pub use people::*;
+
+#[cfg(feature = "persistencemodels")]
+mod launches;
+#[cfg(feature = "persistencemodels")]
+pub use launches::*;
@@ -0,0 +1,377 @@
+//! Arrival and departure events derived from successive people snapshots
+use crate::apimodels::PeopleInSpaceModel;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Format of `update_time` in people snapshots, and so of event timestamps
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+pub fn parse_timestamp(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_str(value, TIMESTAMP_FORMAT).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PeopleEventKind {
+    Arrived,
+    Departed,
+}
+
+/// One person arriving on or leaving a craft, stamped with the update time of
+/// the snapshot the change was first seen in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct PeopleEvent {
+    pub name: String,
+    pub craft: String,
+    pub kind: PeopleEventKind,
+    pub timestamp: String,
+}
+
+/// Append-only log of every arrival and departure, oldest first
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct PeopleEventLog {
+    pub events: Vec<PeopleEvent>,
+}
+
+/// What the event log and the latest snapshot say about one person
+#[derive(Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct PersonHistory {
+    pub name: String,
+    pub in_orbit: bool,
+    /// Craft the person is currently aboard
+    pub craft: Option<String>,
+    pub first_seen: String,
+    /// Update time of the latest snapshot listing them, or of their departure
+    pub last_seen: String,
+    pub events: Vec<PeopleEvent>,
+}
+
+/// One continuous period a person spent aboard a craft
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stint {
+    pub name: String,
+    pub craft: String,
+    pub arrived: DateTime<FixedOffset>,
+    /// `None` while the person is still aboard
+    pub departed: Option<DateTime<FixedOffset>>,
+}
+
+impl Stint {
+    /// Whole days of the stint up to `now`, counting only time after `since`
+    pub fn days(&self, since: Option<DateTime<FixedOffset>>, now: DateTime<FixedOffset>) -> i64 {
+        let start = since.map_or(self.arrived, |since| since.max(self.arrived));
+        let end = self.departed.map_or(now, |departed| departed.min(now));
+        (end - start).num_days().max(0)
+    }
+}
+
+/// Number of people aboard each craft right after the events at `timestamp`;
+/// open-notify reports crews by station, so in practice these are stations
+#[derive(Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct CrewCount {
+    pub timestamp: String,
+    pub crews: BTreeMap<String, usize>,
+}
+
+/// Aggregates over the whole event log
+#[derive(Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct PeopleStats {
+    /// Days spent in orbit this calendar year, summed over everyone
+    pub person_days_this_year: i64,
+    pub crew_timeline: Vec<CrewCount>,
+}
+
+/// Events turning `previous` into `current`; with no previous snapshot
+/// everyone on board counts as arriving
+pub fn diff_people(
+    previous: Option<&PeopleInSpaceModel>,
+    current: &PeopleInSpaceModel,
+) -> Vec<PeopleEvent> {
+    let on_board = |model: &PeopleInSpaceModel, name: &str, craft: &str| {
+        model
+            .people
+            .iter()
+            .any(|person| person.name == name && person.craft == craft)
+    };
+    let event = |name: &str, craft: &str, kind| PeopleEvent {
+        name: name.to_string(),
+        craft: craft.to_string(),
+        kind,
+        timestamp: current.update_time.clone(),
+    };
+
+    let mut events = Vec::new();
+    if let Some(previous) = previous {
+        for person in &previous.people {
+            if !on_board(current, &person.name, &person.craft) {
+                events.push(event(
+                    &person.name,
+                    &person.craft,
+                    PeopleEventKind::Departed,
+                ));
+            }
+        }
+    }
+    for person in &current.people {
+        if !previous.is_some_and(|previous| on_board(previous, &person.name, &person.craft)) {
+            events.push(event(&person.name, &person.craft, PeopleEventKind::Arrived));
+        }
+    }
+    events
+}
+
+impl PeopleEventLog {
+    /// Pair up arrivals and departures; events with unreadable timestamps
+    /// are skipped
+    pub fn stints(&self) -> Vec<Stint> {
+        let mut stints: Vec<Stint> = Vec::new();
+        for event in &self.events {
+            let Some(timestamp) = parse_timestamp(&event.timestamp) else {
+                continue;
+            };
+
+            let open = stints.iter_mut().find(|stint| {
+                stint.departed.is_none() && stint.name == event.name && stint.craft == event.craft
+            });
+            match (event.kind, open) {
+                (PeopleEventKind::Arrived, None) => stints.push(Stint {
+                    name: event.name.clone(),
+                    craft: event.craft.clone(),
+                    arrived: timestamp,
+                    departed: None,
+                }),
+                (PeopleEventKind::Departed, Some(stint)) => stint.departed = Some(timestamp),
+                _ => {}
+            }
+        }
+        stints
+    }
+
+    /// Tracked days for `name` up to `now`: `(current stint, all stints)`,
+    /// where the current stint is `None` unless they are still aboard
+    pub fn days_in_orbit(&self, name: &str, now: DateTime<FixedOffset>) -> (Option<i64>, i64) {
+        let stints: Vec<Stint> = self
+            .stints()
+            .into_iter()
+            .filter(|stint| stint.name == name)
+            .collect();
+
+        let current = stints
+            .iter()
+            .find(|stint| stint.departed.is_none())
+            .map(|stint| stint.days(None, now));
+        let total = stints.iter().map(|stint| stint.days(None, now)).sum();
+        (current, total)
+    }
+
+    pub fn stats(&self, now: DateTime<FixedOffset>) -> PeopleStats {
+        let year_start = NaiveDate::from_ymd_opt(now.year(), 1, 1)
+            .and_then(|day| day.and_hms_opt(0, 0, 0))
+            .and_then(|midnight| now.timezone().from_local_datetime(&midnight).single());
+        let person_days_this_year = self
+            .stints()
+            .iter()
+            .map(|stint| stint.days(year_start, now))
+            .sum();
+
+        let mut crews: BTreeMap<String, usize> = BTreeMap::new();
+        let mut crew_timeline: Vec<CrewCount> = Vec::new();
+        for event in &self.events {
+            let crew = crews.entry(event.craft.clone()).or_default();
+            match event.kind {
+                PeopleEventKind::Arrived => *crew += 1,
+                PeopleEventKind::Departed => *crew = crew.saturating_sub(1),
+            }
+
+            let counts = crews
+                .iter()
+                .filter(|(_, count)| **count > 0)
+                .map(|(craft, count)| (craft.clone(), *count))
+                .collect();
+            match crew_timeline.last_mut() {
+                Some(last) if last.timestamp == event.timestamp => last.crews = counts,
+                _ => crew_timeline.push(CrewCount {
+                    timestamp: event.timestamp.clone(),
+                    crews: counts,
+                }),
+            }
+        }
+
+        PeopleStats {
+            person_days_this_year,
+            crew_timeline,
+        }
+    }
+
+    /// History of the person called `name` (case-insensitive), or `None` if
+    /// they never appeared in the log
+    pub fn person(
+        &self,
+        name: &str,
+        current: Option<&PeopleInSpaceModel>,
+    ) -> Option<PersonHistory> {
+        let events: Vec<PeopleEvent> = self
+            .events
+            .iter()
+            .filter(|event| event.name.eq_ignore_ascii_case(name.trim()))
+            .cloned()
+            .collect();
+        let (first, last) = (events.first()?, events.last()?);
+
+        let aboard = current.and_then(|current| {
+            current
+                .people
+                .iter()
+                .find(|person| person.name == first.name)
+                .map(|person| (person.craft.clone(), current.update_time.clone()))
+        });
+        let (in_orbit, craft, last_seen) = match aboard {
+            Some((craft, update_time)) => (true, Some(craft), update_time),
+            None if current.is_none() && last.kind == PeopleEventKind::Arrived => {
+                (true, Some(last.craft.clone()), last.timestamp.clone())
+            }
+            None => (false, None, last.timestamp.clone()),
+        };
+
+        Some(PersonHistory {
+            name: first.name.clone(),
+            in_orbit,
+            craft,
+            first_seen: first.timestamp.clone(),
+            last_seen,
+            events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apimodels::PersonModel;
+
+    fn snapshot(update_time: &str, people: &[(&str, &str)]) -> PeopleInSpaceModel {
+        PeopleInSpaceModel {
+            update_time: update_time.to_string(),
+            people: people
+                .iter()
+                .map(|(name, craft)| PersonModel {
+                    name: name.to_string(),
+                    craft: craft.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn diff_reports_arrivals_and_departures() {
+        let previous = snapshot(
+            "2023-11-01",
+            &[("Andreas Mogensen", "ISS"), ("Tang Hongbo", "Tiangong")],
+        );
+        let current = snapshot(
+            "2023-11-02",
+            &[("Tang Hongbo", "Tiangong"), ("Loral O'Hara", "ISS")],
+        );
+
+        let events = diff_people(Some(&previous), &current);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name, "Andreas Mogensen");
+        assert_eq!(events[0].kind, PeopleEventKind::Departed);
+        assert_eq!(events[1].name, "Loral O'Hara");
+        assert_eq!(events[1].kind, PeopleEventKind::Arrived);
+        assert_eq!(events[1].timestamp, "2023-11-02");
+        assert_eq!(diff_people(None, &current).len(), 2);
+    }
+
+    #[test]
+    fn person_history_tracks_first_and_last_seen() {
+        let first = snapshot("2023-11-01", &[("Andreas Mogensen", "ISS")]);
+        let second = snapshot("2023-11-02", &[("Loral O'Hara", "ISS")]);
+        let log = PeopleEventLog {
+            events: [
+                diff_people(None, &first),
+                diff_people(Some(&first), &second),
+            ]
+            .concat(),
+        };
+
+        let departed = log.person("andreas mogensen", Some(&second)).unwrap();
+        assert!(!departed.in_orbit);
+        assert_eq!(departed.first_seen, "2023-11-01");
+        assert_eq!(departed.last_seen, "2023-11-02");
+        assert_eq!(departed.events.len(), 2);
+
+        let aboard = log.person("Loral O'Hara", Some(&second)).unwrap();
+        assert!(aboard.in_orbit);
+        assert_eq!(aboard.craft.as_deref(), Some("ISS"));
+
+        assert_eq!(log.person("Nobody", Some(&second)), None);
+    }
+
+    #[test]
+    fn stats_count_days_and_crews() {
+        let log = PeopleEventLog {
+            events: vec![
+                event(
+                    "Andreas Mogensen",
+                    "ISS",
+                    PeopleEventKind::Arrived,
+                    "2022-12-27 00:00:00 +0000",
+                ),
+                event(
+                    "Andreas Mogensen",
+                    "ISS",
+                    PeopleEventKind::Departed,
+                    "2023-03-01 00:00:00 +0000",
+                ),
+                event(
+                    "Loral O'Hara",
+                    "ISS",
+                    PeopleEventKind::Arrived,
+                    "2023-09-15 00:00:00 +0000",
+                ),
+                event(
+                    "Tang Hongbo",
+                    "Tiangong",
+                    PeopleEventKind::Arrived,
+                    "2023-09-15 00:00:00 +0000",
+                ),
+            ],
+        };
+        let now = parse_timestamp("2023-10-01 00:00:00 +0000").unwrap();
+
+        assert_eq!(log.days_in_orbit("Loral O'Hara", now), (Some(16), 16));
+        assert_eq!(log.days_in_orbit("Andreas Mogensen", now), (None, 64));
+
+        let stats = log.stats(now);
+        // 59 days of Mogensen's stint fall in 2023, plus 16 each for the others
+        assert_eq!(stats.person_days_this_year, 91);
+        assert_eq!(stats.crew_timeline.len(), 3);
+        assert_eq!(stats.crew_timeline[0].crews["ISS"], 1);
+        // Nobody is aboard between Mogensen leaving and the next arrivals
+        assert_eq!(
+            stats.crew_timeline[1].timestamp,
+            "2023-03-01 00:00:00 +0000"
+        );
+        assert!(stats.crew_timeline[1].crews.is_empty());
+        assert_eq!(stats.crew_timeline[2].crews["ISS"], 1);
+        assert_eq!(stats.crew_timeline[2].crews["Tiangong"], 1);
+    }
+
+    fn event(name: &str, craft: &str, kind: PeopleEventKind, timestamp: &str) -> PeopleEvent {
+        PeopleEvent {
+            name: name.to_string(),
+            craft: craft.to_string(),
+            kind,
+            timestamp: timestamp.to_string(),
+        }
+    }
+}
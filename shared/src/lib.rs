@@ -1,12 +1,44 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-
 #[cfg(feature = "apimodels")]
 pub mod apimodels;
 
 #[cfg(feature = "persistencemodels")]
 pub mod persistencemodels;
 
+#[cfg(feature = "storage")]
+pub mod storage;
+
+pub mod error;
+
+pub mod craft;
+
+#[cfg(any(feature = "apimodels", feature = "persistencemodels"))]
+pub mod history;
+
+pub mod config;
+
+pub mod validation;
+
+#[cfg(feature = "fetch")]
+pub mod fetch;
+
+#[cfg(feature = "export")]
+pub mod export;
+
+#[cfg(feature = "caching")]
+pub mod caching;
+
+#[cfg(feature = "compression")]
+pub mod compression;
+
+#[cfg(feature = "openapi")]
+pub mod openapi;
+
+#[cfg(feature = "notify")]
+pub mod notify;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
 // /// Individual person in space model
 // #[derive(Debug, Serialize, Deserialize)]
 // pub struct PersonModel {
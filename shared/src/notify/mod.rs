@@ -0,0 +1,364 @@
+//! Signed JSON webhooks delivered to subscribers when something happens in space
+use crate::fetch::Fetcher;
+use crate::storage::{DocumentStore, DocumentStoreExt, StorageError};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+/// `sha256=<hex HMAC of "{timestamp}.{body}">`, keyed with the subscription secret
+pub const SIGNATURE_HEADER: &str = "X-SpaceBits-Signature";
+/// Unix time the delivery was signed at
+pub const TIMESTAMP_HEADER: &str = "X-SpaceBits-Timestamp";
+/// The notification kind, e.g. `launch_slipped`
+pub const EVENT_HEADER: &str = "X-SpaceBits-Event";
+
+/// Prefix of the documents recording deliveries that failed every retry
+pub const DEAD_LETTER_PREFIX: &str = "notify/dead-letter/";
+
+/// How long a delivery is remembered, so it is not repeated on later runs
+const LEDGER_RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A potentially hazardous NEO approaches within some lunar distances
+    HazardousApproach,
+    /// People arrived in or departed from orbit
+    CrewChanged,
+    /// A launch is less than 24 hours, or 1 hour, away
+    LaunchImminent,
+    /// A launch slipped or was scrubbed
+    LaunchSlipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    /// Stable identifier; a notification is delivered once per subscription
+    pub id: String,
+    pub kind: NotificationKind,
+    /// When the event happens or was detected
+    pub occurred_at: String,
+    pub data: serde_json::Value,
+}
+
+/// A webhook endpoint and the notifications it wants
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    /// Kinds to deliver, every kind when empty
+    #[serde(default)]
+    pub kinds: Vec<NotificationKind>,
+    /// Only deliver hazardous approaches closer than this many lunar distances
+    #[serde(default)]
+    pub max_lunar_distance: Option<f64>,
+}
+
+impl Subscription {
+    pub fn wants(&self, notification: &Notification) -> bool {
+        let kind = self.kinds.is_empty() || self.kinds.contains(&notification.kind);
+        let distance = notification.kind != NotificationKind::HazardousApproach
+            || self.max_lunar_distance.is_none_or(|max| {
+                notification.data["miss_distance_lunar"]
+                    .as_f64()
+                    .is_some_and(|distance| distance <= max)
+            });
+        kind && distance
+    }
+}
+
+/// Document listing every subscription
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SubscriptionList {
+    pub subscriptions: Vec<Subscription>,
+}
+
+/// Deliveries already made, keyed `{subscription id}/{notification id}`,
+/// with the unix time they were made
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeliveryLedger {
+    pub delivered: BTreeMap<String, u64>,
+}
+
+/// A delivery that failed every retry, kept for inspection and replay
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub subscription_id: String,
+    pub url: String,
+    pub notification: Notification,
+    pub error: String,
+    pub failed_at: u64,
+}
+
+/// What [`dispatch`] did
+#[derive(Debug, Default, PartialEq)]
+pub struct DispatchReport {
+    pub delivered: usize,
+    pub dead_lettered: usize,
+    /// Already delivered on an earlier run
+    pub skipped: usize,
+}
+
+/// Signature of a delivery made at `timestamp`, the value of [`SIGNATURE_HEADER`]
+pub fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn ledger_key(subscription: &Subscription, notification: &Notification) -> String {
+    format!("{}/{}", subscription.id, notification.id)
+}
+
+/// Deliver every notification to every subscription that wants it and has
+/// not received it yet, moving deliveries that fail every retry to the
+/// dead-letter prefix; `now` is unix time
+pub async fn dispatch(
+    store: &dyn DocumentStore,
+    fetcher: &Fetcher,
+    ledger: &str,
+    subscriptions: &[Subscription],
+    notifications: &[Notification],
+    now: u64,
+) -> Result<DispatchReport, StorageError> {
+    let mut delivered: DeliveryLedger = match store.get(ledger).await {
+        Ok(delivered) => delivered,
+        Err(StorageError::NotFound(_)) => DeliveryLedger::default(),
+        Err(err) => return Err(err),
+    };
+    delivered
+        .delivered
+        .retain(|_, at| now.saturating_sub(*at) < LEDGER_RETENTION_SECS);
+    let mut report = DispatchReport::default();
+
+    for subscription in subscriptions {
+        for notification in notifications {
+            if !subscription.wants(notification) {
+                continue;
+            }
+            let key = ledger_key(subscription, notification);
+            if delivered.delivered.contains_key(&key) {
+                report.skipped += 1;
+                continue;
+            }
+
+            let body = serde_json::to_string(notification)?;
+            let headers = [
+                (SIGNATURE_HEADER, sign(&subscription.secret, now, &body)),
+                (TIMESTAMP_HEADER, now.to_string()),
+                (
+                    EVENT_HEADER,
+                    serde_json::to_value(notification.kind)?
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                ),
+            ];
+
+            match fetcher.post_json(&subscription.url, &body, &headers).await {
+                Ok(_) => report.delivered += 1,
+                Err(err) => {
+                    log::error!(
+                        "Delivering {} to {} failed: {}",
+                        notification.id,
+                        subscription.id,
+                        err
+                    );
+                    let dead_letter = DeadLetter {
+                        subscription_id: subscription.id.clone(),
+                        url: subscription.url.clone(),
+                        notification: notification.clone(),
+                        error: err.to_string(),
+                        failed_at: now,
+                    };
+                    let dead_letter_key = format!(
+                        "{}{}-{}.json",
+                        DEAD_LETTER_PREFIX,
+                        now,
+                        key.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_")
+                    );
+                    store.put(&dead_letter_key, &dead_letter).await?;
+                    report.dead_lettered += 1;
+                }
+            }
+            // Dead-lettered deliveries are recorded too, replaying them is a
+            // manual step rather than something every run retries
+            delivered.delivered.insert(key, now);
+            // Saved after every delivery so a run cut short by the lambda
+            // timeout does not send the same webhooks again next time
+            store.put(ledger, &delivered).await?;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(kind: NotificationKind, data: serde_json::Value) -> Notification {
+        Notification {
+            id: "neo:3542519:2023-11-01".to_string(),
+            kind,
+            occurred_at: "2023-11-01".to_string(),
+            data,
+        }
+    }
+
+    fn subscription() -> Subscription {
+        Subscription {
+            id: "ops".to_string(),
+            url: "http://127.0.0.1:9/hook".to_string(),
+            secret: "secret".to_string(),
+            kinds: Vec::new(),
+            max_lunar_distance: None,
+        }
+    }
+
+    #[test]
+    fn signature_matches_reference_hmac() {
+        // echo -n '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", 1700000000, "{}"),
+            "sha256=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+
+    #[test]
+    fn subscriptions_filter_by_kind_and_distance() {
+        let approach = notification(
+            NotificationKind::HazardousApproach,
+            serde_json::json!({ "miss_distance_lunar": 4.2 }),
+        );
+        let slip = notification(NotificationKind::LaunchSlipped, serde_json::json!({}));
+
+        assert!(subscription().wants(&approach));
+
+        let launches_only = Subscription {
+            kinds: vec![NotificationKind::LaunchSlipped],
+            ..subscription()
+        };
+        assert!(!launches_only.wants(&approach));
+        assert!(launches_only.wants(&slip));
+
+        let close_only = Subscription {
+            max_lunar_distance: Some(2.0),
+            ..subscription()
+        };
+        assert!(!close_only.wants(&approach));
+        assert!(close_only.wants(&slip));
+    }
+
+    #[cfg(feature = "testing")]
+    mod delivery {
+        use super::*;
+        use crate::fetch::RetryPolicy;
+        use crate::storage::MemoryStore;
+        use crate::testing::{StubResponse, StubServer};
+        use std::time::Duration;
+
+        fn fetcher() -> Fetcher {
+            Fetcher::new(RetryPolicy {
+                max_attempts: 2,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(5),
+                attempt_timeout: Duration::from_secs(2),
+            })
+        }
+
+        #[tokio::test]
+        async fn delivers_signed_webhooks_once() {
+            let server = StubServer::builder()
+                .respond("/hook", StubResponse::status(503))
+                .respond("/hook", StubResponse::status(204))
+                .start()
+                .await;
+            let subscriptions = [Subscription {
+                url: server.url("/hook"),
+                ..subscription()
+            }];
+            let notifications = [notification(
+                NotificationKind::HazardousApproach,
+                serde_json::json!({ "miss_distance_lunar": 4.2 }),
+            )];
+            let store = MemoryStore::new();
+
+            let report = dispatch(
+                &store,
+                &fetcher(),
+                "ledger.json",
+                &subscriptions,
+                &notifications,
+                1700000000,
+            )
+            .await
+            .unwrap();
+            assert_eq!(report.delivered, 1);
+
+            let received = server.received();
+            assert_eq!(received.len(), 2);
+            let request = &received[1];
+            assert_eq!(request.method, "POST");
+            assert_eq!(request.header(TIMESTAMP_HEADER), Some("1700000000"));
+            assert_eq!(request.header(EVENT_HEADER), Some("hazardous_approach"));
+            assert_eq!(
+                request.header(SIGNATURE_HEADER),
+                Some(sign("secret", 1700000000, &request.body).as_str())
+            );
+            let body: Notification = serde_json::from_str(&request.body).unwrap();
+            assert_eq!(body, notifications[0]);
+
+            let report = dispatch(
+                &store,
+                &fetcher(),
+                "ledger.json",
+                &subscriptions,
+                &notifications,
+                1700000060,
+            )
+            .await
+            .unwrap();
+            assert_eq!(report.skipped, 1);
+            assert_eq!(server.received().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn failed_deliveries_are_dead_lettered() {
+            let server = StubServer::builder()
+                .respond("/hook", StubResponse::status(500))
+                .start()
+                .await;
+            let subscriptions = [Subscription {
+                url: server.url("/hook"),
+                ..subscription()
+            }];
+            let notifications = [notification(
+                NotificationKind::CrewChanged,
+                serde_json::json!({}),
+            )];
+            let store = MemoryStore::new();
+
+            let report = dispatch(
+                &store,
+                &fetcher(),
+                "ledger.json",
+                &subscriptions,
+                &notifications,
+                1700000000,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(report.dead_lettered, 1);
+            let keys = store.list(DEAD_LETTER_PREFIX).await.unwrap();
+            assert_eq!(keys.len(), 1);
+            let dead_letter: DeadLetter = store.get(&keys[0]).await.unwrap();
+            assert_eq!(dead_letter.subscription_id, "ops");
+            assert!(dead_letter.error.contains("500"));
+        }
+    }
+}
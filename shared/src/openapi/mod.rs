@@ -0,0 +1,622 @@
+//! OpenAPI 3.1 description of the read endpoints, with the JSON Schemas
+//! derived from the response models as its components
+use crate::apimodels::PeopleByCraftResponse;
+use crate::error::{ProblemDetails, PROBLEM_JSON};
+use crate::history::{LaunchHistoryResponse, PeopleEventLog, PersonHistory};
+use crate::persistencemodels::{NearEarthObjectApiResponse, UpcomingLaunchesResponse};
+use schemars::generate::{Contract, SchemaSettings};
+use schemars::SchemaGenerator;
+use serde_json::{json, Map, Value};
+
+pub const OPENAPI_VERSION: &str = "3.1.0";
+
+/// Version of the described API, which follows the `shared` crate's
+pub const API_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Schemas describe what the lambdas serialize, and land under
+/// `#/components/schemas` where the operations refer to them
+fn generator() -> SchemaGenerator {
+    SchemaSettings::draft2020_12()
+        .with(|settings| {
+            settings.definitions_path = "/components/schemas".into();
+            settings.contract = Contract::Serialize;
+            settings.meta_schema = None;
+        })
+        .into_generator()
+}
+
+fn query(name: &str, schema: Value, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": schema,
+    })
+}
+
+fn path(name: &str, schema: Value, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": schema,
+    })
+}
+
+fn format_param() -> Value {
+    query(
+        "format",
+        json!({ "enum": ["json", "csv", "ndjson", "jsonl"] }),
+        "Representation of the response, taking precedence over `Accept`",
+    )
+}
+
+/// A GET operation and the responses it can answer with
+struct Operation {
+    summary: &'static str,
+    parameters: Vec<Value>,
+    ok: Value,
+    /// Statuses besides 200, all answered with a problem document
+    problems: &'static [(u16, &'static str)],
+    api_key: bool,
+}
+
+impl Operation {
+    fn new(summary: &'static str, ok: Value) -> Self {
+        Operation {
+            summary,
+            parameters: Vec::new(),
+            ok,
+            problems: &[],
+            api_key: false,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        let mut responses = Map::new();
+        responses.insert("200".to_string(), self.ok);
+        responses.insert(
+            "304".to_string(),
+            json!({ "description": "Unchanged since the `If-None-Match` or `If-Modified-Since` validator" }),
+        );
+        for (status, description) in self.problems {
+            responses.insert(
+                status.to_string(),
+                json!({
+                    "description": description,
+                    "content": {
+                        (PROBLEM_JSON): { "schema": { "$ref": "#/components/schemas/ProblemDetails" } }
+                    }
+                }),
+            );
+        }
+
+        let mut operation = json!({
+            "summary": self.summary,
+            "parameters": self.parameters,
+            "responses": responses,
+        });
+        if self.api_key {
+            operation["security"] = json!([{ "apiKey": [] }]);
+        }
+        json!({ "get": operation })
+    }
+}
+
+fn json_ok(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+/// JSON, or one CSV or NDJSON row per item
+fn exportable_ok(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": {
+            "application/json": { "schema": schema },
+            "text/csv": { "schema": { "type": "string" } },
+            "application/x-ndjson": { "schema": { "type": "string" } },
+        }
+    })
+}
+
+fn neo_parameters() -> Vec<Value> {
+    vec![
+        query(
+            "hazardous",
+            json!({ "type": "boolean" }),
+            "Only potentially hazardous asteroids, or only the others",
+        ),
+        query(
+            "sentry",
+            json!({ "type": "boolean" }),
+            "Only Sentry objects, or only the others",
+        ),
+        query(
+            "min_diameter_m",
+            json!({ "type": "number" }),
+            "Smallest upper diameter estimate, in meters",
+        ),
+        query(
+            "max_miss_distance_lunar",
+            json!({ "type": "number" }),
+            "Farthest closest approach, in lunar distances",
+        ),
+        query(
+            "sort",
+            json!({ "enum": ["miss_distance", "velocity", "size"] }),
+            "Closest, fastest or largest first",
+        ),
+        query(
+            "limit",
+            json!({ "type": "integer", "minimum": 0 }),
+            "Page size",
+        ),
+        query(
+            "offset",
+            json!({ "type": "integer", "minimum": 0 }),
+            "Objects to skip",
+        ),
+        format_param(),
+    ]
+}
+
+/// Filters shared by the launch listing and the calendar feed
+fn launch_filters() -> Vec<Value> {
+    let text = json!({ "type": "string" });
+    let bound = json!({ "type": "string", "description": "RFC 3339 time or YYYY-MM-DD" });
+    vec![
+        query("provider", text.clone(), "Provider name or slug"),
+        query("vehicle", text.clone(), "Vehicle name or slug"),
+        query("pad", text.clone(), "Pad name"),
+        query("country", text.clone(), "Country of the pad"),
+        query("state", text.clone(), "State of the pad"),
+        query("tag", text.clone(), "Launch tag"),
+        query(
+            "suborbital",
+            json!({ "type": "boolean" }),
+            "Only suborbital launches, or only orbital ones",
+        ),
+        query("from", bound.clone(), "Earliest launch time"),
+        query("to", bound, "Latest launch time"),
+        query("q", text, "Text to look for in the name and descriptions"),
+    ]
+}
+
+fn launch_parameters() -> Vec<Value> {
+    let mut parameters = launch_filters();
+    parameters.extend([
+        query(
+            "limit",
+            json!({ "type": "integer", "minimum": 1 }),
+            "Page size",
+        ),
+        query(
+            "cursor",
+            json!({ "type": "string" }),
+            "`next_cursor` of the previous page",
+        ),
+        format_param(),
+    ]);
+    parameters
+}
+
+/// The OpenAPI document for `/people`, `/neo`, `/launches` and the routes
+/// beneath them
+pub fn document() -> Value {
+    let mut generator = generator();
+    let people = generator.subschema_for::<PeopleByCraftResponse>();
+    let people_events = generator.subschema_for::<PeopleEventLog>();
+    let person = generator.subschema_for::<PersonHistory>();
+    let neo = generator.subschema_for::<NearEarthObjectApiResponse>();
+    let launches = generator.subschema_for::<UpcomingLaunchesResponse>();
+    let launch_history = generator.subschema_for::<LaunchHistoryResponse>();
+    generator.subschema_for::<ProblemDetails>();
+
+    let date = path(
+        "date",
+        json!({ "type": "string", "format": "date" }),
+        "Archived day, YYYY-MM-DD",
+    );
+    let neo_day_parameters = {
+        let mut parameters = neo_parameters();
+        parameters.insert(0, date);
+        parameters
+    };
+
+    let operations = [
+        (
+            "/people",
+            Operation {
+                problems: &[(404, "No people have been collected yet")],
+                ..Operation::new(
+                    "People in space, grouped by craft",
+                    json_ok("People in space", people.to_value()),
+                )
+            },
+        ),
+        (
+            "/people/history",
+            Operation::new(
+                "Every recorded arrival and departure",
+                json_ok("Event log, oldest first", people_events.to_value()),
+            ),
+        ),
+        (
+            "/people/{name}",
+            Operation {
+                parameters: vec![path(
+                    "name",
+                    json!({ "type": "string" }),
+                    "Name of the person",
+                )],
+                problems: &[(404, "The person has not been seen in orbit")],
+                ..Operation::new(
+                    "History of one person",
+                    json_ok("The person's history", person.to_value()),
+                )
+            },
+        ),
+        (
+            "/neo",
+            Operation {
+                parameters: neo_parameters(),
+                problems: &[
+                    (400, "Invalid query parameter"),
+                    (404, "No objects have been collected yet"),
+                ],
+                api_key: true,
+                ..Operation::new(
+                    "Near earth objects of the latest feed",
+                    exportable_ok("Near earth objects", neo.clone().to_value()),
+                )
+            },
+        ),
+        (
+            "/neo/{date}",
+            Operation {
+                parameters: neo_day_parameters,
+                problems: &[
+                    (400, "Invalid date or query parameter"),
+                    (404, "Nothing archived for the day"),
+                ],
+                api_key: true,
+                ..Operation::new(
+                    "Near earth objects archived for one day",
+                    exportable_ok("Near earth objects", neo.to_value()),
+                )
+            },
+        ),
+        (
+            "/launches",
+            Operation {
+                parameters: launch_parameters(),
+                problems: &[
+                    (400, "Invalid query parameter"),
+                    (404, "No launches have been collected yet"),
+                ],
+                ..Operation::new(
+                    "Upcoming launches",
+                    exportable_ok("A page of upcoming launches", launches.to_value()),
+                )
+            },
+        ),
+        (
+            "/launches.ics",
+            Operation {
+                parameters: launch_filters(),
+                problems: &[
+                    (400, "Invalid query parameter"),
+                    (404, "No launches have been collected yet"),
+                ],
+                ..Operation::new(
+                    "Upcoming launches as an iCalendar feed",
+                    json!({
+                        "description": "One event per matching launch, ignoring `limit` and `cursor`",
+                        "content": { "text/calendar": { "schema": { "type": "string" } } }
+                    }),
+                )
+            },
+        ),
+        (
+            "/launches/{id}/history",
+            Operation {
+                parameters: vec![path(
+                    "id",
+                    json!({ "type": "integer", "minimum": 0 }),
+                    "Launch id",
+                )],
+                problems: &[
+                    (400, "The id is not a number"),
+                    (404, "No launch with the id"),
+                ],
+                ..Operation::new(
+                    "Schedule changes of one launch",
+                    json_ok("Changes, oldest first", launch_history.to_value()),
+                )
+            },
+        ),
+    ];
+    let paths: Map<String, Value> = operations
+        .into_iter()
+        .map(|(path, operation)| (path.to_string(), operation.into_value()))
+        .collect();
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "SpaceBits API",
+            "version": API_VERSION,
+            "description": "People in space, near earth objects and upcoming launches",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": generator.take_definitions(true),
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": "x-api-key" }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apimodels::{CraftGroup, EnrichedPerson};
+    use crate::craft::Craft;
+    use crate::history::{CrewCount, PeopleStats};
+    use crate::persistencemodels::{NearEarthObjectModel, UpcomingLaunches};
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    /// Validator for one component, resolving its references within the document
+    fn validator(component: &str) -> jsonschema::Validator {
+        let mut root = document();
+        root["$ref"] = json!(format!("#/components/schemas/{}", component));
+        jsonschema::draft202012::new(&root).unwrap()
+    }
+
+    fn errors(component: &str, payload: &Value) -> Vec<String> {
+        validator(component)
+            .iter_errors(payload)
+            .map(|err| format!("{} at {}", err, err.instance_path()))
+            .collect()
+    }
+
+    /// Checks the JSON text a lambda would send: going through `to_value`
+    /// would silently merge repeated keys
+    fn assert_matches(component: &str, payload: &impl Serialize) {
+        let wire = serde_json::to_string(payload).unwrap();
+        let payload: serde_json::Value = serde_json::from_str(&wire).unwrap();
+        // Parsing keeps the last of repeated keys, leaving a shorter document
+        assert_eq!(
+            serde_json::to_string(&payload).unwrap().len(),
+            wire.len(),
+            "{} sample repeats a key: {}",
+            component,
+            wire
+        );
+        let errors = errors(component, &payload);
+        assert!(errors.is_empty(), "{} sample: {:?}", component, errors);
+    }
+
+    fn neo_response() -> NearEarthObjectApiResponse {
+        let data: NearEarthObjectModel = serde_json::from_value(json!({
+            "links": { "next": null, "prev": null, "self": null },
+            "element_count": 1,
+            "updated_date_time": "2023-11-01 05:00:00 UTC",
+            "near_earth_objects": [{
+                "id": "3542519",
+                "neo_reference_id": "3542519",
+                "name": "(2010 PK9)",
+                "nasa_jpl_url": "https://ssd.jpl.nasa.gov/sbdb.cgi?sstr=3542519",
+                "absolute_magnitude_h": 21.2,
+                "estimated_diameter": {
+                    "kilometers": { "estimated_diameter_min": 0.146, "estimated_diameter_max": 0.326 },
+                    "meters": { "estimated_diameter_min": 146.0, "estimated_diameter_max": 326.6 },
+                    "miles": { "estimated_diameter_min": 0.090, "estimated_diameter_max": 0.202 },
+                    "feet": { "estimated_diameter_min": 479.2, "estimated_diameter_max": 1071.5 }
+                },
+                "is_potentially_hazardous_asteroid": true,
+                "close_approach_data": [{
+                    "close_approach_date": "2023-11-01",
+                    "epoch_date_close_approach": 1698824040000_i64,
+                    "relative_velocity": {
+                        "kilometers_per_second": 14.09,
+                        "kilometers_per_hour": 50753.32,
+                        "miles_per_hour": 31536.34
+                    },
+                    "miss_distance": {
+                        "astronomical": 0.25,
+                        "lunar": 97.3,
+                        "kilometers": 37421297.45,
+                        "miles": 23252337.63
+                    },
+                    "orbiting_body": "Earth"
+                }],
+                "is_sentry_object": false,
+                "links": { "next": null, "prev": null, "self": "http://api.nasa.gov/neo/rest/v1/neo/3542519" }
+            }]
+        }))
+        .unwrap();
+
+        NearEarthObjectApiResponse {
+            updated_date_time: data.updated_date_time.clone(),
+            data,
+            total_count: 1,
+            filtered_count: 1,
+        }
+    }
+
+    fn launches_response() -> UpcomingLaunchesResponse {
+        let launches: UpcomingLaunches = serde_json::from_value(json!({
+            "valid_auth": true,
+            "count": 1,
+            "limit": 25,
+            "total": 1,
+            "last_page": 1,
+            "result": [{
+                "id": 1,
+                "cospar_id": null,
+                "sort_date": "1699000000",
+                "name": "Starlink Group 6-26",
+                "provider": { "id": 1, "name": "SpaceX", "slug": "spacex" },
+                "vehicle": { "id": 1, "name": "Falcon 9", "company_id": 1, "slug": "falcon-9", "pad": null },
+                "pad": {
+                    "id": 2,
+                    "name": "SLC-40",
+                    "location": {
+                        "id": 61,
+                        "name": "Cape Canaveral SFS",
+                        "state": "FL",
+                        "statename": "Florida",
+                        "country": "United States",
+                        "slug": "cape-canaveral-sfs"
+                    }
+                },
+                "missions": [{ "id": 1, "name": "Starlink Group 6-26", "description": null }],
+                "mission_description": "",
+                "launch_description": "A SpaceX Falcon 9 rocket will launch the Starlink Group 6-26 mission",
+                "win_open": "2023-11-03T08:45:00Z",
+                "t0": null,
+                "win_close": null,
+                "date_str": "Nov 03",
+                "tags": [{ "id": 1, "text": "Starlink" }],
+                "slug": "starlink-6-26",
+                "weather_summary": null,
+                "weather_temp": 0,
+                "weather_condition": null,
+                "weather_wind_mph": null,
+                "weather_icon": null,
+                "weather_updated": null,
+                "quick_text": null,
+                "suborbital": false,
+                "modified": "2023-11-01T00:00:00+00:00"
+            }],
+            "generated_at": "2023-11-01T00:05:00Z"
+        }))
+        .unwrap();
+
+        UpcomingLaunchesResponse {
+            launches,
+            date: "2023-11-01T00:05:00Z".to_string(),
+            total_count: 1,
+            filtered_count: 1,
+            next_cursor: None,
+        }
+    }
+
+    /// Every `$ref` in `value`
+    fn references(value: &Value, found: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    found.push(reference.clone());
+                }
+                map.values().for_each(|value| references(value, found));
+            }
+            Value::Array(values) => values.iter().for_each(|value| references(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn document_describes_the_read_endpoints() {
+        let document = document();
+
+        assert_eq!(document["openapi"], "3.1.0");
+        assert_eq!(document["info"]["version"], API_VERSION);
+        for path in ["/people", "/neo", "/launches", "/launches.ics"] {
+            assert!(document["paths"][path]["get"]["responses"]["200"].is_object());
+        }
+        assert_eq!(
+            document["paths"]["/neo"]["get"]["security"],
+            json!([{ "apiKey": [] }])
+        );
+
+        let mut found = Vec::new();
+        references(&document, &mut found);
+        assert!(!found.is_empty());
+        for reference in found {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected reference {}", reference));
+            assert!(
+                document["components"]["schemas"][name].is_object(),
+                "{} does not resolve",
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn sample_payloads_match_their_schemas() {
+        let people = [("Jasmin Moghbeli", "ISS"), ("Tang Hongbo", "Shenzhou 17")];
+        let crafts: Vec<(&str, Craft)> = people
+            .iter()
+            .map(|(name, craft)| (*name, Craft::from_name(craft)))
+            .collect();
+        let other = Craft::from_name("Dragon Endeavour");
+        assert_matches(
+            "PeopleByCraftResponse",
+            &PeopleByCraftResponse {
+                update_time: "2023-11-01 12:00:00 +0000",
+                people: crafts
+                    .iter()
+                    .map(|(name, craft)| EnrichedPerson {
+                        name,
+                        craft,
+                        days_in_orbit_current_stint: Some(66),
+                        tracked_days: Some(66),
+                    })
+                    .chain([EnrichedPerson {
+                        name: "Andreas Mogensen",
+                        craft: &other,
+                        days_in_orbit_current_stint: None,
+                        tracked_days: None,
+                    }])
+                    .collect(),
+                crafts: vec![
+                    CraftGroup {
+                        name: "ISS",
+                        info: crafts[0].1.info(),
+                        people: vec!["Jasmin Moghbeli"],
+                    },
+                    CraftGroup {
+                        name: "Dragon Endeavour",
+                        info: None,
+                        people: vec!["Andreas Mogensen"],
+                    },
+                ],
+                stats: Some(PeopleStats {
+                    person_days_this_year: 1234,
+                    crew_timeline: vec![CrewCount {
+                        timestamp: "2023-11-01 12:00:00 +0000".to_string(),
+                        crews: BTreeMap::from([("ISS".to_string(), 7)]),
+                    }],
+                }),
+            },
+        );
+        assert_matches("NearEarthObjectApiResponse", &neo_response());
+        assert_matches("UpcomingLaunchesResponse", &launches_response());
+        assert_matches(
+            "ProblemDetails",
+            &crate::error::ApiError::NotFound("gone".to_string()).problem(),
+        );
+    }
+
+    #[test]
+    fn schemas_reject_payloads_of_the_wrong_shape() {
+        let mut neo = serde_json::to_value(neo_response()).unwrap();
+        neo.as_object_mut().unwrap().remove("total_count");
+        assert!(!errors("NearEarthObjectApiResponse", &neo).is_empty());
+
+        let mut launches = serde_json::to_value(launches_response()).unwrap();
+        launches["launches"]["result"][0]["id"] = json!("1");
+        assert!(!errors("UpcomingLaunchesResponse", &launches).is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+//! Owned conversions from the NeoWs API models into the persisted models
+use super::*;
+use crate::apimodels;
+use std::fmt;
+
+/// A numeric field from the API that could not be parsed
+#[derive(Debug, PartialEq)]
+pub struct InvalidNumber {
+    pub field: &'static str,
+    pub value: String,
+    /// Id of the near earth object the field belongs to, when known
+    pub id: Option<String>,
+}
+
+impl fmt::Display for InvalidNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.id {
+            Some(id) => write!(
+                f,
+                "Invalid {} for near earth object {}: {:?}",
+                self.field, id, self.value
+            ),
+            None => write!(f, "Invalid {}: {:?}", self.field, self.value),
+        }
+    }
+}
+
+impl std::error::Error for InvalidNumber {}
+
+/// NeoWs encodes velocities and distances as strings, parse them once here
+fn parse_number(field: &'static str, value: String) -> Result<f64, InvalidNumber> {
+    value.trim().parse().map_err(|_| InvalidNumber {
+        field,
+        value,
+        id: None,
+    })
+}
+
+impl From<apimodels::ApiResponseLink> for ApiLinks {
+    fn from(links: apimodels::ApiResponseLink) -> Self {
+        ApiLinks {
+            next: links.next,
+            prev: links.prev,
+            this: links.this,
+        }
+    }
+}
+
+impl From<apimodels::EstimatedDiameterValues> for EstimatedDiameterValues {
+    fn from(values: apimodels::EstimatedDiameterValues) -> Self {
+        EstimatedDiameterValues {
+            estimated_diameter_min: values.estimated_diameter_min,
+            estimated_diameter_max: values.estimated_diameter_max,
+        }
+    }
+}
+
+impl From<apimodels::EstimatedDiameter> for EstimatedDiameter {
+    fn from(diameter: apimodels::EstimatedDiameter) -> Self {
+        EstimatedDiameter {
+            kilometers: diameter.kilometers.into(),
+            meters: diameter.meters.into(),
+            miles: diameter.miles.into(),
+            feet: diameter.feet.into(),
+        }
+    }
+}
+
+impl TryFrom<apimodels::RelativeVelocity> for RelativeVelocity {
+    type Error = InvalidNumber;
+
+    fn try_from(velocity: apimodels::RelativeVelocity) -> Result<Self, Self::Error> {
+        Ok(RelativeVelocity {
+            kilometers_per_second: parse_number(
+                "relative_velocity.kilometers_per_second",
+                velocity.kilometers_per_second,
+            )?,
+            kilometers_per_hour: parse_number(
+                "relative_velocity.kilometers_per_hour",
+                velocity.kilometers_per_hour,
+            )?,
+            miles_per_hour: parse_number(
+                "relative_velocity.miles_per_hour",
+                velocity.miles_per_hour,
+            )?,
+        })
+    }
+}
+
+impl TryFrom<apimodels::MissDistance> for MissDistance {
+    type Error = InvalidNumber;
+
+    fn try_from(distance: apimodels::MissDistance) -> Result<Self, Self::Error> {
+        Ok(MissDistance {
+            astronomical: parse_number("miss_distance.astronomical", distance.astronomical)?,
+            lunar: parse_number("miss_distance.lunar", distance.lunar)?,
+            kilometers: parse_number("miss_distance.kilometers", distance.kilometers)?,
+            miles: parse_number("miss_distance.miles", distance.miles)?,
+        })
+    }
+}
+
+impl TryFrom<apimodels::CloseApproachData> for CloseApproachData {
+    type Error = InvalidNumber;
+
+    fn try_from(approach: apimodels::CloseApproachData) -> Result<Self, Self::Error> {
+        Ok(CloseApproachData {
+            close_approach_date: approach.close_approach_date,
+            epoch_date_close_approach: approach.epoch_date_close_approach,
+            relative_velocity: approach.relative_velocity.try_into()?,
+            miss_distance: approach.miss_distance.try_into()?,
+            orbiting_body: approach.orbiting_body,
+        })
+    }
+}
+
+impl TryFrom<apimodels::NearEarthObject> for NearEarthObject {
+    type Error = InvalidNumber;
+
+    fn try_from(neo: apimodels::NearEarthObject) -> Result<Self, Self::Error> {
+        let close_approach_data = neo
+            .close_approach_data
+            .into_iter()
+            .map(CloseApproachData::try_from)
+            .collect::<Result<_, _>>()
+            .map_err(|err| InvalidNumber {
+                id: Some(neo.id.clone()),
+                ..err
+            })?;
+
+        Ok(NearEarthObject {
+            id: neo.id,
+            neo_reference_id: neo.neo_reference_id,
+            name: neo.name,
+            nasa_jpl_url: neo.nasa_jpl_url.replace("http://", "https://"),
+            absolute_magnitude_h: neo.absolute_magnitude_h,
+            estimated_diameter: neo.estimated_diameter.into(),
+            is_potentially_hazardous_asteroid: neo.is_potentially_hazardous_asteroid,
+            close_approach_data,
+            is_sentry_object: neo.is_sentry_object,
+            links: neo.links.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED_OBJECT: &str = r#"{
+        "links": { "self": "http://api.nasa.gov/neo/rest/v1/neo/3542519" },
+        "id": "3542519",
+        "neo_reference_id": "3542519",
+        "name": "(2010 PK9)",
+        "nasa_jpl_url": "http://ssd.jpl.nasa.gov/sbdb.cgi?sstr=3542519",
+        "absolute_magnitude_h": 21.2,
+        "estimated_diameter": {
+            "kilometers": { "estimated_diameter_min": 0.1460679, "estimated_diameter_max": 0.3266178 },
+            "meters": { "estimated_diameter_min": 146.0679, "estimated_diameter_max": 326.6178 },
+            "miles": { "estimated_diameter_min": 0.0907629, "estimated_diameter_max": 0.2029518 },
+            "feet": { "estimated_diameter_min": 479.2256, "estimated_diameter_max": 1071.5807 }
+        },
+        "is_potentially_hazardous_asteroid": true,
+        "close_approach_data": [{
+            "close_approach_date": "2023-11-01",
+            "epoch_date_close_approach": 1698824040000,
+            "relative_velocity": {
+                "kilometers_per_second": "14.0981445787",
+                "kilometers_per_hour": "50753.3204832406",
+                "miles_per_hour": "31536.3476155474"
+            },
+            "miss_distance": {
+                "astronomical": "0.2501474152",
+                "lunar": "97.3073445128",
+                "kilometers": "37421297.455286024",
+                "miles": "23252337.6311003712"
+            },
+            "orbiting_body": "Earth"
+        }],
+        "is_sentry_object": false
+    }"#;
+
+    fn feed_object() -> apimodels::NearEarthObject {
+        serde_json::from_str(FEED_OBJECT).unwrap()
+    }
+
+    #[test]
+    fn converts_feed_object_to_persisted_model() {
+        let neo = NearEarthObject::try_from(feed_object()).unwrap();
+
+        assert_eq!(neo.id, "3542519");
+        assert_eq!(
+            neo.nasa_jpl_url,
+            "https://ssd.jpl.nasa.gov/sbdb.cgi?sstr=3542519"
+        );
+        assert_eq!(
+            neo.estimated_diameter.meters.estimated_diameter_max,
+            326.6178
+        );
+        assert_eq!(
+            neo.links.this.as_deref(),
+            Some("http://api.nasa.gov/neo/rest/v1/neo/3542519")
+        );
+
+        let approach = &neo.close_approach_data[0];
+        assert_eq!(
+            approach.relative_velocity.kilometers_per_second,
+            14.0981445787
+        );
+        assert_eq!(approach.miss_distance.lunar, 97.3073445128);
+        assert_eq!(approach.orbiting_body, "Earth");
+    }
+
+    #[test]
+    fn persisted_model_round_trips_through_json() {
+        let neo = NearEarthObject::try_from(feed_object()).unwrap();
+        let json = serde_json::to_string(&neo).unwrap();
+        let read: NearEarthObject = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&read).unwrap(),
+            serde_json::to_value(&neo).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_number_names_field_and_object() {
+        let mut neo = feed_object();
+        neo.close_approach_data[0].miss_distance.lunar = "n/a".to_string();
+
+        let err = NearEarthObject::try_from(neo).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid miss_distance.lunar for near earth object 3542519: \"n/a\""
+        );
+    }
+}
@@ -1,16 +1,55 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+#[cfg(feature = "apimodels")]
+mod conversions;
+
+#[cfg(feature = "apimodels")]
+pub use conversions::InvalidNumber;
 
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct NearEarthObjectApiResponse {
     pub data: NearEarthObjectModel,
     pub updated_date_time: String,
+    /// Objects in the stored document before any query filters were applied
+    pub total_count: usize,
+    /// Objects matching the query filters, before `limit`/`offset`
+    pub filtered_count: usize,
+}
+
+impl NearEarthObjectApiResponse {
+    /// Response carrying `page`, one page of the objects in `stored` that
+    /// `filtered_count` objects matched
+    pub fn new(
+        stored: &NearEarthObjectModel,
+        filtered_count: usize,
+        page: Vec<NearEarthObject>,
+    ) -> Self {
+        NearEarthObjectApiResponse {
+            updated_date_time: stored.updated_date_time.clone(),
+            data: NearEarthObjectModel {
+                links: stored.links.clone(),
+                element_count: page.len() as i32,
+                updated_date_time: stored.updated_date_time.clone(),
+                near_earth_objects: page,
+            },
+            total_count: stored.near_earth_objects.len(),
+            filtered_count,
+        }
+    }
+
+    /// Response to a request without any query parameters
+    pub fn unfiltered(stored: &NearEarthObjectModel) -> Self {
+        let objects = stored.near_earth_objects.clone();
+        Self::new(stored, objects.len(), objects)
+    }
 }
 
 /// Models for storing NEO data in the database
 /// Used because the API response is not directly compatible with the database
 /// (the API response is a HashMap, and the database is a Vec)
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct NearEarthObjectModel {
     pub links: ApiLinks,
     pub element_count: i32,
@@ -18,7 +57,51 @@ pub struct NearEarthObjectModel {
     pub near_earth_objects: Vec<NearEarthObject>,
 }
 
+/// Key of the manifest listing every archived NEO day
+pub const NEO_ARCHIVE_INDEX_KEY: &str = "neo/index.json";
+
+/// Archive key for a `YYYY-MM-DD` day, e.g. `neo/2023/11/01.json`
+/// Returns `None` when the date is not in that format
+pub fn neo_archive_key(date: &str) -> Option<String> {
+    let parts: Vec<&str> = date.split('-').collect();
+    let valid = parts.len() == 3
+        && parts
+            .iter()
+            .zip([4, 2, 2])
+            .all(|(part, len)| part.len() == len && part.chars().all(|c| c.is_ascii_digit()));
+
+    if !valid {
+        return None;
+    }
+
+    Some(format!("neo/{}/{}/{}.json", parts[0], parts[1], parts[2]))
+}
+
+/// Manifest of the per-day NEO snapshots in the archive
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NearEarthObjectArchiveIndex {
+    pub updated_date_time: String,
+    pub days: Vec<NearEarthObjectArchiveDay>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+pub struct NearEarthObjectArchiveDay {
+    pub date: String,
+    pub key: String,
+    pub element_count: i32,
+}
+
+impl NearEarthObjectArchiveIndex {
+    /// Add or replace the entry for a day, keeping the days in date order
+    pub fn upsert(&mut self, day: NearEarthObjectArchiveDay) {
+        self.days.retain(|existing| existing.date != day.date);
+        self.days.push(day);
+        self.days.sort_by(|a, b| a.date.cmp(&b.date));
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct NearEarthObject {
     pub id: String,
     pub neo_reference_id: String,
@@ -32,7 +115,8 @@ pub struct NearEarthObject {
     pub links: ApiLinks,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct ApiLinks {
     pub next: Option<String>,
     pub prev: Option<String>,
@@ -40,7 +124,8 @@ pub struct ApiLinks {
     pub this: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct EstimatedDiameter {
     pub kilometers: EstimatedDiameterValues,
     pub meters: EstimatedDiameterValues,
@@ -48,13 +133,15 @@ pub struct EstimatedDiameter {
     pub feet: EstimatedDiameterValues,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct EstimatedDiameterValues {
     pub estimated_diameter_min: f64,
     pub estimated_diameter_max: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct CloseApproachData {
     pub close_approach_date: String,
     pub epoch_date_close_approach: i64,
@@ -63,23 +150,52 @@ pub struct CloseApproachData {
     pub orbiting_body: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct RelativeVelocity {
-    pub kilometers_per_second: String,
-    pub kilometers_per_hour: String,
-    pub miles_per_hour: String,
+    #[serde(deserialize_with = "number_or_string")]
+    pub kilometers_per_second: f64,
+    #[serde(deserialize_with = "number_or_string")]
+    pub kilometers_per_hour: f64,
+    #[serde(deserialize_with = "number_or_string")]
+    pub miles_per_hour: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct MissDistance {
-    pub astronomical: String,
-    pub lunar: String,
-    pub kilometers: String,
-    pub miles: String,
+    #[serde(deserialize_with = "number_or_string")]
+    pub astronomical: f64,
+    #[serde(deserialize_with = "number_or_string")]
+    pub lunar: f64,
+    #[serde(deserialize_with = "number_or_string")]
+    pub kilometers: f64,
+    #[serde(deserialize_with = "number_or_string")]
+    pub miles: f64,
+}
+
+/// Accept plain numbers as well as the string-encoded numbers
+/// (e.g. `"12345.678"`) in documents written by earlier collectors
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(f64),
+        String(String),
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(value) => Ok(value),
+        NumberOrString::String(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| de::Error::custom(format!("invalid number: {:?}", value))),
+    }
 }
 
 // Upcoming Launches
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct UpcomingLaunches {
     pub valid_auth: bool,
     pub count: u32,
@@ -87,9 +203,78 @@ pub struct UpcomingLaunches {
     pub total: u64,
     pub last_page: u64,
     pub result: Vec<UpcomingLaunchesLaunch>,
+    /// When the collector assembled the document (RFC 3339), absent in older documents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<String>,
+}
+
+impl UpcomingLaunches {
+    /// Time the document was generated; documents written before the
+    /// collector recorded it fall back to the most recent launch modification
+    pub fn generated_at(&self) -> String {
+        self.generated_at.clone().unwrap_or_else(|| {
+            self.result
+                .iter()
+                .map(|launch| launch.modified.as_str())
+                .max()
+                .unwrap_or_default()
+                .to_string()
+        })
+    }
 }
 
+/// Body of the `/launches` response
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct UpcomingLaunchesResponse {
+    pub launches: UpcomingLaunches,
+    /// When the launch data was generated
+    pub date: String,
+    /// Launches in the stored document before any query filters were applied
+    pub total_count: usize,
+    /// Launches matching the query filters, across every page
+    pub filtered_count: usize,
+    /// Pass as `cursor` to fetch the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+impl UpcomingLaunchesResponse {
+    /// Response carrying `page`, one page of the launches in `stored` that
+    /// `filtered_count` launches matched
+    pub fn new(
+        stored: &UpcomingLaunches,
+        filtered_count: usize,
+        page: Vec<UpcomingLaunchesLaunch>,
+        next_cursor: Option<String>,
+    ) -> Self {
+        UpcomingLaunchesResponse {
+            launches: UpcomingLaunches {
+                valid_auth: stored.valid_auth,
+                count: page.len() as u32,
+                limit: stored.limit,
+                total: stored.total,
+                last_page: stored.last_page,
+                result: page,
+                generated_at: stored.generated_at.clone(),
+            },
+            date: stored.generated_at(),
+            total_count: stored.result.len(),
+            filtered_count,
+            next_cursor,
+        }
+    }
+
+    /// Response to a request without any query parameters, every launch in
+    /// listing order
+    pub fn unfiltered(stored: &UpcomingLaunches) -> Self {
+        let mut launches = stored.result.clone();
+        launches.sort_by_key(UpcomingLaunchesLaunch::listing_order);
+        Self::new(stored, launches.len(), launches, None)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct UpcomingLaunchesLaunch {
     pub id: u64,
     pub cospar_id: Option<String>,
@@ -119,14 +304,24 @@ pub struct UpcomingLaunchesLaunch {
     pub modified: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl UpcomingLaunchesLaunch {
+    /// Position in the listing: by `sort_date`, launches without a usable
+    /// one last, then by `id`
+    pub fn listing_order(&self) -> (u64, u64) {
+        (self.sort_date.parse().unwrap_or(u64::MAX), self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct UpcomingLaunchProvider {
     pub id: u64,
     pub name: String,
     pub slug: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct UpcomingLaunchVehicle {
     pub id: u64,
     pub name: String,
@@ -135,14 +330,16 @@ pub struct UpcomingLaunchVehicle {
     pub pad: Option<UpcomingLaunchPad>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct UpcomingLaunchPad {
     pub id: u64,
     pub name: String,
     pub location: UpcomingLaunchPadLocation,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct UpcomingLaunchPadLocation {
     pub id: u64,
     pub name: String,
@@ -153,15 +350,85 @@ pub struct UpcomingLaunchPadLocation {
     pub slug: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct UpcomingLaunchMission {
     pub id: u64,
     pub name: String,
     pub description: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct UpcomingLaunchTag {
     pub id: u64,
     pub text: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_key_is_partitioned_by_date() {
+        assert_eq!(
+            neo_archive_key("2023-11-01").as_deref(),
+            Some("neo/2023/11/01.json")
+        );
+    }
+
+    #[test]
+    fn archive_key_rejects_malformed_dates() {
+        assert_eq!(neo_archive_key("2023-11"), None);
+        assert_eq!(neo_archive_key("2023-1-01"), None);
+        assert_eq!(neo_archive_key("../../etc"), None);
+    }
+
+    #[test]
+    fn index_upsert_replaces_and_sorts_days() {
+        let day = |date: &str, element_count| NearEarthObjectArchiveDay {
+            date: date.to_string(),
+            key: neo_archive_key(date).unwrap(),
+            element_count,
+        };
+
+        let mut index = NearEarthObjectArchiveIndex::default();
+        index.upsert(day("2023-11-02", 3));
+        index.upsert(day("2023-11-01", 4));
+        index.upsert(day("2023-11-02", 5));
+
+        let days: Vec<_> = index
+            .days
+            .iter()
+            .map(|day| (day.date.as_str(), day.element_count))
+            .collect();
+        assert_eq!(days, vec![("2023-11-01", 4), ("2023-11-02", 5)]);
+    }
+
+    #[test]
+    fn miss_distance_accepts_numbers_and_legacy_strings() {
+        let legacy: MissDistance = serde_json::from_str(
+            r#"{"astronomical":"0.25","lunar":"97.25","kilometers":"37400000.5","miles":"23239000"}"#,
+        )
+        .unwrap();
+        let current: MissDistance = serde_json::from_str(
+            r#"{"astronomical":0.25,"lunar":97.25,"kilometers":37400000.5,"miles":23239000}"#,
+        )
+        .unwrap();
+
+        assert_eq!(legacy.lunar, 97.25);
+        assert_eq!(current.kilometers, legacy.kilometers);
+        assert_eq!(
+            serde_json::to_value(&legacy).unwrap()["lunar"],
+            serde_json::json!(97.25)
+        );
+    }
+
+    #[test]
+    fn malformed_legacy_number_is_rejected() {
+        let result = serde_json::from_str::<RelativeVelocity>(
+            r#"{"kilometers_per_second":"fast","kilometers_per_hour":"1","miles_per_hour":"1"}"#,
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use super::{decode, DocumentMeta, DocumentStore, StorageError};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Documents kept before the one validated longest ago is dropped
+const MAX_ENTRIES: usize = 32;
+
+#[derive(Clone)]
+struct Entry {
+    document: Arc<dyn Any + Send + Sync>,
+    meta: DocumentMeta,
+    checked_at: Instant,
+}
+
+/// Parsed documents by key and type
+///
+/// An entry is trusted for `ttl` after it was read, then only reused while
+/// the store still reports the same version of the document, which for S3
+/// costs a HEAD request instead of a GET and a parse.
+pub struct DocumentCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<(String, TypeId), Entry>>,
+}
+
+impl fmt::Debug for DocumentCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DocumentCache")
+            .field("ttl", &self.ttl)
+            .field("entries", &self.entries.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl DocumentCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The parsed document under `key`, read from `store` when there is no
+    /// usable entry, along with when it was last written
+    pub async fn get<T, S>(
+        &self,
+        store: &S,
+        key: &str,
+    ) -> Result<(Arc<T>, Option<SystemTime>), StorageError>
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+        S: DocumentStore + ?Sized,
+    {
+        let id = (key.to_string(), TypeId::of::<T>());
+        let cached = self.entries.lock().unwrap().get(&id).cloned();
+
+        if let Some(entry) = cached {
+            let current = if entry.checked_at.elapsed() < self.ttl {
+                true
+            } else if entry.meta.version.is_some()
+                && store.meta(key).await?.version == entry.meta.version
+            {
+                if let Some(entry) = self.entries.lock().unwrap().get_mut(&id) {
+                    entry.checked_at = Instant::now();
+                }
+                true
+            } else {
+                false
+            };
+
+            if current {
+                if let Ok(document) = entry.document.downcast::<T>() {
+                    return Ok((document, entry.meta.last_modified));
+                }
+            }
+        }
+
+        let (bytes, meta) = store.get_bytes_with_meta(key).await?;
+        let document = Arc::new(serde_json::from_slice::<T>(&decode(bytes)?)?);
+        let last_modified = meta.last_modified;
+        self.insert(
+            id,
+            Entry {
+                document: document.clone(),
+                meta,
+                checked_at: Instant::now(),
+            },
+        );
+
+        Ok((document, last_modified))
+    }
+
+    fn insert(&self, id: (String, TypeId), entry: Entry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(&id) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.checked_at)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(id, entry);
+    }
+
+    /// Drop every entry for `key`, whatever type it was parsed as
+    pub fn invalidate(&self, key: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|(cached, _), _| cached != key);
+    }
+}
+
+/// Any store with a [`DocumentCache`] in front of it; writes and deletes
+/// made through it invalidate the cached document
+#[derive(Debug)]
+pub struct CachedStore<S> {
+    inner: S,
+    cache: DocumentCache,
+}
+
+impl<S> CachedStore<S> {
+    pub fn new(inner: S, ttl: Duration) -> Self {
+        Self {
+            inner,
+            cache: DocumentCache::new(ttl),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: DocumentStore> DocumentStore for CachedStore<S> {
+    async fn get_bytes(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        self.inner.get_bytes(key).await
+    }
+
+    async fn get_bytes_with_meta(
+        &self,
+        key: &str,
+    ) -> Result<(Vec<u8>, DocumentMeta), StorageError> {
+        self.inner.get_bytes_with_meta(key).await
+    }
+
+    async fn meta(&self, key: &str) -> Result<DocumentMeta, StorageError> {
+        self.inner.meta(key).await
+    }
+
+    async fn put_bytes(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError> {
+        self.cache.invalidate(key);
+        self.inner.put_bytes(key, body).await
+    }
+
+    async fn put_encoded(
+        &self,
+        key: &str,
+        body: Vec<u8>,
+        content_encoding: &str,
+    ) -> Result<(), StorageError> {
+        self.cache.invalidate(key);
+        self.inner.put_encoded(key, body, content_encoding).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        self.inner.list(prefix).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.cache.invalidate(key);
+        self.inner.delete(key).await
+    }
+
+    fn document_cache(&self) -> Option<&DocumentCache> {
+        Some(&self.cache)
+    }
+}
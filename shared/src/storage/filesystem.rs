@@ -0,0 +1,123 @@
+use super::{DocumentMeta, DocumentStore, StorageError};
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Documents stored as files under a local directory, one file per key
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        key.split('/')
+            .filter(|part| !part.is_empty() && *part != "..")
+            .fold(self.root.clone(), |path, part| path.join(part))
+    }
+
+    fn key_for(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<_> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+        Some(parts.join("/"))
+    }
+}
+
+/// Modification time and size stand in for a version, as they would for a
+/// web server's ETag
+fn meta_of(metadata: &std::fs::Metadata) -> DocumentMeta {
+    let last_modified = metadata.modified().ok();
+    let version = last_modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| format!("{}-{}", modified.as_nanos(), metadata.len()));
+    DocumentMeta {
+        last_modified,
+        version,
+    }
+}
+
+#[async_trait]
+impl DocumentStore for FileStore {
+    async fn get_bytes(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        match tokio::fs::read(self.path_for(key)).await {
+            Ok(bytes) => Ok(bytes),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Err(StorageError::NotFound(key.to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn get_bytes_with_meta(
+        &self,
+        key: &str,
+    ) -> Result<(Vec<u8>, DocumentMeta), StorageError> {
+        let bytes = self.get_bytes(key).await?;
+        let meta = tokio::fs::metadata(self.path_for(key))
+            .await
+            .map(|metadata| meta_of(&metadata))
+            .unwrap_or_default();
+        Ok((bytes, meta))
+    }
+
+    async fn meta(&self, key: &str) -> Result<DocumentMeta, StorageError> {
+        match tokio::fs::metadata(self.path_for(key)).await {
+            Ok(metadata) => Ok(meta_of(&metadata)),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Err(StorageError::NotFound(key.to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn put_bytes(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError> {
+        let path = self.path_for(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, body).await?;
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        let mut keys = Vec::new();
+        let mut pending = vec![self.root.clone()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    pending.push(path);
+                } else if let Some(key) = self.key_for(&path) {
+                    if key.starts_with(prefix) {
+                        keys.push(key);
+                    }
+                }
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_file(self.path_for(key)).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
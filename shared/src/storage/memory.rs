@@ -0,0 +1,76 @@
+use super::{DocumentMeta, DocumentStore, StorageError};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Documents held in memory, for unit tests and throwaway local runs
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    documents: Mutex<BTreeMap<String, (Vec<u8>, DocumentMeta)>>,
+    /// Counts writes, giving every stored document a distinct version
+    writes: AtomicU64,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DocumentStore for MemoryStore {
+    async fn get_bytes(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        Ok(self.get_bytes_with_meta(key).await?.0)
+    }
+
+    async fn get_bytes_with_meta(
+        &self,
+        key: &str,
+    ) -> Result<(Vec<u8>, DocumentMeta), StorageError> {
+        self.documents
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| StorageError::NotFound(key.to_string()))
+    }
+
+    async fn meta(&self, key: &str) -> Result<DocumentMeta, StorageError> {
+        self.documents
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|(_, meta)| meta.clone())
+            .ok_or_else(|| StorageError::NotFound(key.to_string()))
+    }
+
+    async fn put_bytes(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError> {
+        let meta = DocumentMeta {
+            last_modified: Some(SystemTime::now()),
+            version: Some(self.writes.fetch_add(1, Ordering::Relaxed).to_string()),
+        };
+        self.documents
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), (body, meta));
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        Ok(self
+            .documents
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.documents.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+mod cache;
+mod filesystem;
+mod memory;
+#[cfg(feature = "s3")]
+mod s3;
+
+#[cfg(feature = "s3")]
+pub use self::s3::S3Store;
+pub use cache::{CachedStore, DocumentCache};
+pub use filesystem::FileStore;
+pub use memory::MemoryStore;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// No document is stored under the requested key
+    NotFound(String),
+    /// The stored document could not be (de)serialized
+    Serialization(serde_json::Error),
+    /// The backing store itself failed
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NotFound(key) => write!(f, "No document found for key {}", key),
+            StorageError::Serialization(err) => write!(f, "Invalid document: {}", err),
+            StorageError::Backend(err) => write!(f, "Storage backend error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::NotFound(_) => None,
+            StorageError::Serialization(err) => Some(err),
+            StorageError::Backend(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError::Serialization(err)
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> Self {
+        StorageError::Backend(Box::new(err))
+    }
+}
+
+/// First bytes of a gzip stream, which no JSON document starts with
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether stored bytes are gzip-compressed rather than plain JSON
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_MAGIC)
+}
+
+/// Key of the response to a request without query parameters, rendered
+/// from the document at `key` and stored gzip-compressed by its collector
+pub fn rendered_key(key: &str) -> String {
+    format!("rendered/{}", key)
+}
+
+/// The JSON of a stored document, inflating it when it was stored compressed
+pub(crate) fn decode(bytes: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+    if !is_gzip(&bytes) {
+        return Ok(bytes);
+    }
+    let mut json = Vec::new();
+    GzDecoder::new(&bytes[..]).read_to_end(&mut json)?;
+    Ok(json)
+}
+
+/// What the backend knows about a stored document besides its contents
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentMeta {
+    /// When the document was last written
+    pub last_modified: Option<SystemTime>,
+    /// Opaque identifier that changes whenever the document is rewritten,
+    /// such as the S3 ETag
+    pub version: Option<String>,
+}
+
+/// Raw key/value access to wherever the JSON documents live
+///
+/// Keys are `/` separated paths such as `near_earth_objects.json` or
+/// `neo/2023/11/01.json`, regardless of the backend.
+#[async_trait]
+pub trait DocumentStore: Send + Sync {
+    async fn get_bytes(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// The document along with whatever the backend keeps track of about it
+    async fn get_bytes_with_meta(
+        &self,
+        key: &str,
+    ) -> Result<(Vec<u8>, DocumentMeta), StorageError> {
+        Ok((self.get_bytes(key).await?, DocumentMeta::default()))
+    }
+
+    /// [`DocumentMeta`] without the contents, cheap on backends that can
+    /// answer it without reading the document
+    async fn meta(&self, key: &str) -> Result<DocumentMeta, StorageError> {
+        Ok(self.get_bytes_with_meta(key).await?.1)
+    }
+
+    async fn put_bytes(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError>;
+
+    /// Store a body already compressed with `content_encoding`, which
+    /// backends that keep object metadata record alongside it
+    async fn put_encoded(
+        &self,
+        key: &str,
+        body: Vec<u8>,
+        content_encoding: &str,
+    ) -> Result<(), StorageError> {
+        let _ = content_encoding;
+        self.put_bytes(key, body).await
+    }
+
+    /// All keys starting with `prefix`, sorted
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError>;
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// Parsed documents kept across requests, if this store has them
+    fn document_cache(&self) -> Option<&DocumentCache> {
+        None
+    }
+}
+
+/// Typed JSON helpers available on every [`DocumentStore`], including trait objects
+#[async_trait]
+pub trait DocumentStoreExt {
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, StorageError>;
+
+    /// [`get`](Self::get) along with the time the document was last written
+    async fn get_modified<T: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<(T, Option<SystemTime>), StorageError>;
+
+    /// A parsed document shared with earlier requests when the store has a
+    /// [`DocumentCache`], read afresh otherwise
+    async fn get_cached<T: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        key: &str,
+    ) -> Result<(Arc<T>, Option<SystemTime>), StorageError>;
+
+    async fn put<T: Serialize + Sync>(&self, key: &str, value: &T) -> Result<(), StorageError>;
+
+    /// [`put`](Self::put) the document gzip-compressed; every read helper
+    /// inflates it again
+    async fn put_compressed<T: Serialize + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), StorageError>;
+}
+
+#[async_trait]
+impl<S: DocumentStore + ?Sized> DocumentStoreExt for S {
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, StorageError> {
+        let bytes = decode(self.get_bytes(key).await?)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    async fn get_modified<T: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<(T, Option<SystemTime>), StorageError> {
+        let (bytes, meta) = self.get_bytes_with_meta(key).await?;
+        Ok((serde_json::from_slice(&decode(bytes)?)?, meta.last_modified))
+    }
+
+    async fn get_cached<T: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        key: &str,
+    ) -> Result<(Arc<T>, Option<SystemTime>), StorageError> {
+        match self.document_cache() {
+            Some(cache) => cache.get(self, key).await,
+            None => {
+                let (document, modified) = self.get_modified(key).await?;
+                Ok((Arc::new(document), modified))
+            }
+        }
+    }
+
+    async fn put<T: Serialize + Sync>(&self, key: &str, value: &T) -> Result<(), StorageError> {
+        let json = serde_json::to_vec(value)?;
+        self.put_bytes(key, json).await
+    }
+
+    async fn put_compressed<T: Serialize + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), StorageError> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        serde_json::to_writer(&mut encoder, value)?;
+        encoder.flush()?;
+        self.put_encoded(key, encoder.finish()?, "gzip").await
+    }
+}
+
+/// Pick the store for a function: the local directory when one is
+/// configured, otherwise the configured S3 bucket, either behind a
+/// [`DocumentCache`] that lives as long as the function instance
+#[cfg(feature = "s3")]
+pub async fn from_config(config: &crate::config::Config) -> Box<dyn DocumentStore> {
+    let ttl = config.document_cache_ttl;
+    match &config.local_storage_dir {
+        Some(dir) => Box::new(CachedStore::new(FileStore::new(dir), ttl)),
+        None => Box::new(CachedStore::new(
+            S3Store::from_env(&config.bucket_name).await,
+            ttl,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Document {
+        name: String,
+        count: u32,
+    }
+
+    async fn exercise(store: &dyn DocumentStore) {
+        let doc = Document {
+            name: "ISS".to_string(),
+            count: 7,
+        };
+
+        store.put("people/current.json", &doc).await.unwrap();
+        store.put("people/previous.json", &doc).await.unwrap();
+        store.put("launches.json", &doc).await.unwrap();
+
+        let read: Document = store.get("people/current.json").await.unwrap();
+        assert_eq!(read, doc);
+
+        let (read, modified) = store
+            .get_modified::<Document>("people/current.json")
+            .await
+            .unwrap();
+        assert_eq!(read, doc);
+        assert!(modified.is_some_and(|modified| modified <= SystemTime::now()));
+
+        let meta = store.meta("people/current.json").await.unwrap();
+        store.put("people/current.json", &doc).await.unwrap();
+        assert_ne!(
+            store.meta("people/current.json").await.unwrap().version,
+            meta.version
+        );
+
+        assert_eq!(
+            store.list("people/").await.unwrap(),
+            vec!["people/current.json", "people/previous.json"]
+        );
+
+        store.delete("people/current.json").await.unwrap();
+        let missing = store.get::<Document>("people/current.json").await;
+        assert!(matches!(missing, Err(StorageError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn memory_store_round_trip() {
+        exercise(&MemoryStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn file_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise(&FileStore::new(dir.path())).await;
+    }
+
+    #[tokio::test]
+    async fn cached_store_round_trip() {
+        exercise(&CachedStore::new(
+            MemoryStore::new(),
+            Duration::from_secs(60),
+        ))
+        .await;
+    }
+
+    #[tokio::test]
+    async fn cached_documents_are_shared_until_rewritten() {
+        let store = CachedStore::new(MemoryStore::new(), Duration::from_secs(60));
+        let doc = Document {
+            name: "ISS".to_string(),
+            count: 7,
+        };
+        store.put("people.json", &doc).await.unwrap();
+
+        let (first, _) = store.get_cached::<Document>("people.json").await.unwrap();
+        let (second, _) = store.get_cached::<Document>("people.json").await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let doc = Document { count: 8, ..doc };
+        store.put("people.json", &doc).await.unwrap();
+        let (third, _) = store.get_cached::<Document>("people.json").await.unwrap();
+        assert_eq!(*third, doc);
+    }
+
+    #[tokio::test]
+    async fn expired_documents_are_revalidated() {
+        let store = MemoryStore::new();
+        let cache = DocumentCache::new(Duration::ZERO);
+        let doc = Document {
+            name: "ISS".to_string(),
+            count: 7,
+        };
+        store.put("people.json", &doc).await.unwrap();
+
+        let (first, _) = cache
+            .get::<Document, _>(&store, "people.json")
+            .await
+            .unwrap();
+        let (second, _) = cache
+            .get::<Document, _>(&store, "people.json")
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Written behind the cache's back, noticed through the version
+        let doc = Document { count: 8, ..doc };
+        store.put("people.json", &doc).await.unwrap();
+        let (third, _) = cache
+            .get::<Document, _>(&store, "people.json")
+            .await
+            .unwrap();
+        assert_eq!(*third, doc);
+
+        store.delete("people.json").await.unwrap();
+        let missing = cache.get::<Document, _>(&store, "people.json").await;
+        assert!(matches!(missing, Err(StorageError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn compressed_documents_read_back_transparently() {
+        let dir = tempfile::tempdir().unwrap();
+        let stores: [Box<dyn DocumentStore>; 3] = [
+            Box::new(MemoryStore::new()),
+            Box::new(FileStore::new(dir.path())),
+            Box::new(CachedStore::new(
+                MemoryStore::new(),
+                Duration::from_secs(60),
+            )),
+        ];
+        let doc = Document {
+            name: "ISS".repeat(100),
+            count: 7,
+        };
+
+        for store in &stores {
+            store.put_compressed("people.json", &doc).await.unwrap();
+
+            let raw = store.get_bytes("people.json").await.unwrap();
+            assert!(raw.starts_with(&GZIP_MAGIC));
+            assert!(raw.len() < serde_json::to_vec(&doc).unwrap().len());
+
+            assert_eq!(store.get::<Document>("people.json").await.unwrap(), doc);
+            let (cached, _) = store.get_cached::<Document>("people.json").await.unwrap();
+            assert_eq!(*cached, doc);
+        }
+    }
+
+    #[tokio::test]
+    async fn corrupt_document_is_a_serialization_error() {
+        let store = MemoryStore::new();
+        store.put_bytes("bad.json", b"{".to_vec()).await.unwrap();
+
+        let result = store.get::<Document>("bad.json").await;
+        assert!(matches!(result, Err(StorageError::Serialization(_))));
+    }
+}
@@ -0,0 +1,179 @@
+use super::{DocumentMeta, DocumentStore, StorageError};
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use std::time::SystemTime;
+
+/// Documents stored as objects in an S3 bucket
+#[derive(Debug, Clone)]
+pub struct S3Store {
+    client: Client,
+    bucket: String,
+}
+
+impl S3Store {
+    pub fn new(client: Client, bucket: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket: bucket.into(),
+        }
+    }
+
+    /// Build a client from the Lambda environment's AWS configuration
+    pub async fn from_env(bucket: impl Into<String>) -> Self {
+        let config = aws_config::load_from_env().await;
+        Self::new(Client::new(&config), bucket)
+    }
+
+    /// Recording the `Content-Encoding` lets the object be served straight
+    /// from the bucket too; the SDK hands the stored bytes back untouched
+    async fn put_object(
+        &self,
+        key: &str,
+        body: Vec<u8>,
+        content_encoding: Option<&str>,
+    ) -> Result<(), StorageError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type("application/json")
+            .set_content_encoding(content_encoding.map(str::to_string))
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(backend_error)?;
+        Ok(())
+    }
+}
+
+fn backend_error(err: impl std::error::Error + Send + Sync + 'static) -> StorageError {
+    StorageError::Backend(Box::new(err))
+}
+
+#[async_trait]
+impl DocumentStore for S3Store {
+    async fn get_bytes(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        Ok(self.get_bytes_with_meta(key).await?.0)
+    }
+
+    async fn get_bytes_with_meta(
+        &self,
+        key: &str,
+    ) -> Result<(Vec<u8>, DocumentMeta), StorageError> {
+        let data = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(data) => data,
+            Err(err) => {
+                let err = err.into_service_error();
+                return Err(if err.is_no_such_key() {
+                    StorageError::NotFound(key.to_string())
+                } else {
+                    backend_error(err)
+                });
+            }
+        };
+
+        let meta = DocumentMeta {
+            last_modified: data
+                .last_modified()
+                .and_then(|modified| SystemTime::try_from(*modified).ok()),
+            version: data.e_tag().map(str::to_string),
+        };
+        let bytes = data.body.collect().await.map_err(backend_error)?;
+        Ok((bytes.into_bytes().to_vec(), meta))
+    }
+
+    async fn meta(&self, key: &str) -> Result<DocumentMeta, StorageError> {
+        let data = match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(data) => data,
+            Err(err) => {
+                let err = err.into_service_error();
+                return Err(if err.is_not_found() {
+                    StorageError::NotFound(key.to_string())
+                } else {
+                    backend_error(err)
+                });
+            }
+        };
+
+        Ok(DocumentMeta {
+            last_modified: data
+                .last_modified()
+                .and_then(|modified| SystemTime::try_from(*modified).ok()),
+            version: data.e_tag().map(str::to_string),
+        })
+    }
+
+    async fn put_bytes(&self, key: &str, body: Vec<u8>) -> Result<(), StorageError> {
+        self.put_object(key, body, None).await
+    }
+
+    async fn put_encoded(
+        &self,
+        key: &str,
+        body: Vec<u8>,
+        content_encoding: &str,
+    ) -> Result<(), StorageError> {
+        self.put_object(key, body, Some(content_encoding)).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, StorageError> {
+        let mut keys = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let output = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .map_err(backend_error)?;
+
+            keys.extend(
+                output
+                    .contents()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|object| object.key().map(str::to_string)),
+            );
+
+            match output.next_continuation_token() {
+                Some(token) if output.is_truncated() => {
+                    continuation_token = Some(token.to_string())
+                }
+                _ => break,
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(backend_error)?;
+        Ok(())
+    }
+}
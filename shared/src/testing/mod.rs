@@ -0,0 +1,199 @@
+//! A local HTTP server that replays canned upstream responses in tests
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// One canned response
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubResponse {
+    pub fn json(body: impl Into<String>) -> Self {
+        StubResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.into(),
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        StubResponse {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    fn to_http(&self) -> String {
+        let mut response = format!("HTTP/1.1 {} Stub\r\n", self.status);
+        for (name, value) in &self.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str(&format!(
+            "content-length: {}\r\nconnection: close\r\n\r\n{}",
+            self.body.len(),
+            self.body
+        ));
+        response
+    }
+}
+
+/// A request the stub server received
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub method: String,
+    /// Path and query string
+    pub target: String,
+    /// Header names are lower-cased
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl ReceivedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Read one request, waiting for the whole body announced by `content-length`
+async fn read_request(socket: &mut tokio::net::TcpStream) -> ReceivedRequest {
+    let mut data = Vec::new();
+    let mut buffer = vec![0u8; 16 * 1024];
+    let (head, body_start, content_length) = loop {
+        let read = socket.read(&mut buffer).await.unwrap_or_default();
+        data.extend_from_slice(&buffer[..read]);
+        let end = data.windows(4).position(|window| window == b"\r\n\r\n");
+        if let Some(end) = end {
+            let head = String::from_utf8_lossy(&data[..end]).to_string();
+            let content_length = head
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            break (head, end + 4, content_length);
+        }
+        if read == 0 {
+            break (String::from_utf8_lossy(&data).to_string(), data.len(), 0);
+        }
+    };
+
+    while data.len() < body_start + content_length {
+        let read = socket.read(&mut buffer).await.unwrap_or_default();
+        if read == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..read]);
+    }
+
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    ReceivedRequest {
+        method: request_line.next().unwrap_or_default().to_string(),
+        target: request_line.next().unwrap_or_default().to_string(),
+        headers: lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect(),
+        body: String::from_utf8_lossy(&data[body_start.min(data.len())..]).to_string(),
+    }
+}
+
+/// Responses per path, served in order with the last one repeated
+#[derive(Debug, Default)]
+pub struct StubServerBuilder {
+    routes: HashMap<String, Vec<StubResponse>>,
+}
+
+impl StubServerBuilder {
+    /// Queue `response` for requests to `path`, ignoring the query string
+    pub fn respond(mut self, path: &str, response: StubResponse) -> Self {
+        self.routes
+            .entry(path.to_string())
+            .or_default()
+            .push(response);
+        self
+    }
+
+    pub async fn start(self) -> StubServer {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("stub server could not bind");
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let routes = self.routes;
+
+        tokio::spawn(async move {
+            let mut served: HashMap<String, usize> = HashMap::new();
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let request = read_request(&mut socket).await;
+                let path = request
+                    .target
+                    .split('?')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+
+                let response = match routes.get(&path) {
+                    Some(responses) => {
+                        let hit = served.entry(path).or_default();
+                        let response = &responses[(*hit).min(responses.len() - 1)];
+                        *hit += 1;
+                        response.clone()
+                    }
+                    None => StubResponse::status(404),
+                };
+
+                recorded.lock().unwrap().push(request);
+                let _ = socket.write_all(response.to_http().as_bytes()).await;
+            }
+        });
+
+        StubServer { base_url, requests }
+    }
+}
+
+/// Handle to a running stub server, stopped with the test's runtime
+#[derive(Debug)]
+pub struct StubServer {
+    base_url: String,
+    requests: Arc<Mutex<Vec<ReceivedRequest>>>,
+}
+
+impl StubServer {
+    pub fn builder() -> StubServerBuilder {
+        StubServerBuilder::default()
+    }
+
+    /// Absolute URL of `path` on the stub server
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Request targets (path and query) received so far, in order
+    pub fn requests(&self) -> Vec<String> {
+        self.received()
+            .into_iter()
+            .map(|request| request.target)
+            .collect()
+    }
+
+    /// Every request received so far, in order
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
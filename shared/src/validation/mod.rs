@@ -0,0 +1,289 @@
+//! Sanity checks run by the collectors before they replace a stored document
+#[cfg(feature = "persistencemodels")]
+use std::collections::HashSet;
+use std::fmt;
+
+/// Key prefix rejected payloads are written under
+pub const QUARANTINE_PREFIX: &str = "quarantine/";
+
+/// More people than have ever been in space at once, by a wide margin
+#[cfg(feature = "apimodels")]
+const MAX_PEOPLE_IN_SPACE: usize = 50;
+
+/// Why a freshly collected document was not allowed to replace the stored one
+#[derive(Debug, PartialEq)]
+pub enum Rejection {
+    /// The upstream returned no items at all
+    Empty,
+    /// The document contradicts itself or has missing fields
+    Invalid(String),
+    /// The size moved further from the stored document than the policy allows
+    ChangeTooLarge { previous: f64, current: f64 },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Empty => write!(f, "Upstream returned no data"),
+            Rejection::Invalid(reason) => write!(f, "Invalid document: {}", reason),
+            Rejection::ChangeTooLarge { previous, current } => write!(
+                f,
+                "Size changed from {} to {}, more than the allowed ratio",
+                previous, current
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// A collected document that can be checked before it is stored
+pub trait Snapshot {
+    /// Size compared between the stored and the new document
+    fn size(&self) -> f64;
+
+    /// Structural checks that do not depend on the stored document
+    fn check(&self) -> Result<(), Rejection>;
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotPolicy {
+    /// Largest accepted relative change in [`Snapshot::size`], e.g. 0.5 for ±50%
+    pub max_change_ratio: f64,
+    /// Store accepted snapshots gzip-compressed
+    pub compressed: bool,
+    /// Accept a candidate rejected only for its size once this many runs in
+    /// a row were quarantined with payloads of about the same size, taking
+    /// the change as real rather than an upstream glitch; 0 never does
+    pub accept_after_rejections: usize,
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        SnapshotPolicy {
+            max_change_ratio: 0.5,
+            compressed: false,
+            accept_after_rejections: 3,
+        }
+    }
+}
+
+impl SnapshotPolicy {
+    /// Check `candidate` on its own and against the `current` stored document
+    pub fn validate<T: Snapshot>(
+        &self,
+        candidate: &T,
+        current: Option<&T>,
+    ) -> Result<(), Rejection> {
+        candidate.check()?;
+
+        let Some(current) = current else {
+            return Ok(());
+        };
+        let (previous, size) = (current.size(), candidate.size());
+        if previous > 0.0 && ((size - previous) / previous).abs() > self.max_change_ratio {
+            return Err(Rejection::ChangeTooLarge {
+                previous,
+                current: size,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Prefix of every quarantined payload meant for `key`
+pub fn quarantine_dir(key: &str) -> String {
+    format!("{}{}/", QUARANTINE_PREFIX, key.trim_end_matches(".json"))
+}
+
+/// Quarantine key for a payload that was meant to be stored under `key`
+pub fn quarantine_key(key: &str, rejected_at: u64) -> String {
+    format!("{}{}.json", quarantine_dir(key), rejected_at)
+}
+
+#[cfg(feature = "storage")]
+mod store;
+#[cfg(feature = "storage")]
+pub use store::{
+    replace_snapshot, write_rendered, write_snapshot, QuarantinedSnapshot, SnapshotError,
+    SnapshotOutcome,
+};
+
+#[cfg(feature = "apimodels")]
+impl Snapshot for crate::apimodels::PeopleInSpaceModel {
+    fn size(&self) -> f64 {
+        self.people.len() as f64
+    }
+
+    fn check(&self) -> Result<(), Rejection> {
+        if self.people.is_empty() {
+            return Err(Rejection::Empty);
+        }
+        if self.people.len() > MAX_PEOPLE_IN_SPACE {
+            return Err(Rejection::Invalid(format!(
+                "{} people in space is implausible",
+                self.people.len()
+            )));
+        }
+        if let Some(person) = self
+            .people
+            .iter()
+            .find(|person| person.name.trim().is_empty() || person.craft.trim().is_empty())
+        {
+            return Err(Rejection::Invalid(format!(
+                "Person is missing a name or craft: {:?}",
+                person
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "persistencemodels")]
+impl Snapshot for crate::persistencemodels::NearEarthObjectModel {
+    /// Objects per day, so feeds covering a different number of days compare fairly
+    fn size(&self) -> f64 {
+        let days: HashSet<_> = self
+            .near_earth_objects
+            .iter()
+            .filter_map(|neo| neo.close_approach_data.first())
+            .map(|approach| approach.close_approach_date.as_str())
+            .collect();
+
+        self.near_earth_objects.len() as f64 / days.len().max(1) as f64
+    }
+
+    fn check(&self) -> Result<(), Rejection> {
+        if self.near_earth_objects.is_empty() {
+            return Err(Rejection::Empty);
+        }
+        if self.element_count as usize != self.near_earth_objects.len() {
+            return Err(Rejection::Invalid(format!(
+                "element_count is {} but {} objects are present",
+                self.element_count,
+                self.near_earth_objects.len()
+            )));
+        }
+        if let Some(neo) = self
+            .near_earth_objects
+            .iter()
+            .find(|neo| neo.id.is_empty() || neo.close_approach_data.is_empty())
+        {
+            return Err(Rejection::Invalid(format!(
+                "Near earth object {:?} has no id or close approach data",
+                neo.name
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "persistencemodels")]
+impl Snapshot for crate::persistencemodels::UpcomingLaunches {
+    fn size(&self) -> f64 {
+        self.result.len() as f64
+    }
+
+    fn check(&self) -> Result<(), Rejection> {
+        if !self.valid_auth {
+            return Err(Rejection::Invalid(
+                "Launch API did not accept the key".to_string(),
+            ));
+        }
+        if self.result.is_empty() {
+            return Err(Rejection::Empty);
+        }
+        if self.count as usize != self.result.len() {
+            return Err(Rejection::Invalid(format!(
+                "count is {} but {} launches are present",
+                self.count,
+                self.result.len()
+            )));
+        }
+        let mut ids = HashSet::new();
+        if let Some(launch) = self.result.iter().find(|launch| !ids.insert(launch.id)) {
+            return Err(Rejection::Invalid(format!(
+                "Launch {} appears more than once",
+                launch.id
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "apimodels"))]
+mod tests {
+    use super::*;
+    use crate::apimodels::{PeopleInSpaceModel, PersonModel};
+
+    fn people(names: &[&str]) -> PeopleInSpaceModel {
+        PeopleInSpaceModel {
+            update_time: "2023-11-01 00:00:00 +0000".to_string(),
+            people: names
+                .iter()
+                .map(|name| PersonModel {
+                    name: name.to_string(),
+                    craft: "ISS".to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn empty_document_is_rejected() {
+        let policy = SnapshotPolicy::default();
+
+        assert_eq!(policy.validate(&people(&[]), None), Err(Rejection::Empty));
+    }
+
+    #[test]
+    fn missing_fields_are_rejected() {
+        let policy = SnapshotPolicy::default();
+
+        let result = policy.validate(&people(&["Jasmin Moghbeli", " "]), None);
+        assert!(matches!(result, Err(Rejection::Invalid(_))));
+    }
+
+    #[test]
+    fn default_people_policy_accepts_crew_rotations() {
+        let config = crate::config::Config::default();
+        let policy = config.snapshot_policy(config.people_max_change_ratio);
+        let three = people(&["a", "b", "c"]);
+        let seven = people(&["a", "b", "c", "d", "e", "f", "g"]);
+
+        assert!(policy.validate(&seven, Some(&three)).is_ok());
+        assert!(policy.validate(&three, Some(&seven)).is_ok());
+    }
+
+    #[test]
+    fn change_ratio_is_enforced_against_stored_document() {
+        let policy = SnapshotPolicy {
+            max_change_ratio: 0.5,
+            ..SnapshotPolicy::default()
+        };
+        let stored = people(&["a", "b", "c", "d"]);
+
+        assert!(policy
+            .validate(&people(&["a", "b", "c"]), Some(&stored))
+            .is_ok());
+        assert_eq!(
+            policy.validate(&people(&["a"]), Some(&stored)),
+            Err(Rejection::ChangeTooLarge {
+                previous: 4.0,
+                current: 1.0
+            })
+        );
+    }
+
+    #[test]
+    fn quarantine_key_is_namespaced_by_target() {
+        assert_eq!(
+            quarantine_key("people.json", 1698796800),
+            "quarantine/people/1698796800.json"
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use super::{quarantine_dir, quarantine_key, Rejection, Snapshot, SnapshotPolicy};
+use crate::storage::{rendered_key, DocumentStore, DocumentStoreExt, StorageError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What [`write_snapshot`] did with the candidate document
+#[derive(Debug, PartialEq)]
+pub enum SnapshotOutcome {
+    Written,
+    /// The stored document was kept and the candidate written to `key` instead
+    Quarantined {
+        key: String,
+        rejection: Rejection,
+    },
+}
+
+/// Document stored under a quarantine key
+#[derive(Debug, Serialize)]
+pub struct QuarantinedSnapshot<'a, T> {
+    pub target_key: &'a str,
+    pub reason: String,
+    pub rejected_at: u64,
+    pub payload: &'a T,
+}
+
+/// The parts of a [`QuarantinedSnapshot`] read back when deciding whether a
+/// run of rejections confirms a change
+#[derive(Debug, Deserialize)]
+struct QuarantineRecord<T> {
+    rejected_at: u64,
+    payload: T,
+}
+
+/// Why [`replace_snapshot`] did not replace the stored document
+#[derive(Debug)]
+pub enum SnapshotError {
+    Storage(StorageError),
+    /// The candidate failed validation and was written to `quarantine_key`
+    Quarantined {
+        target_key: String,
+        quarantine_key: String,
+        rejection: Rejection,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Storage(err) => write!(f, "{}", err),
+            SnapshotError::Quarantined {
+                target_key,
+                quarantine_key,
+                rejection,
+            } => write!(
+                f,
+                "Kept the stored {}: {} (rejected data written to {})",
+                target_key, rejection, quarantine_key
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Storage(err) => Some(err),
+            SnapshotError::Quarantined { rejection, .. } => Some(rejection),
+        }
+    }
+}
+
+impl From<StorageError> for SnapshotError {
+    fn from(err: StorageError) -> Self {
+        SnapshotError::Storage(err)
+    }
+}
+
+/// [`write_snapshot`] for collectors, which fail the run when the stored
+/// document was kept so the quarantine shows up as an error
+pub async fn replace_snapshot<T>(
+    store: &dyn DocumentStore,
+    key: &str,
+    candidate: &T,
+    policy: &SnapshotPolicy,
+) -> Result<(), SnapshotError>
+where
+    T: Snapshot + Serialize + DeserializeOwned + Sync,
+{
+    match write_snapshot(store, key, candidate, policy).await? {
+        SnapshotOutcome::Written => Ok(()),
+        SnapshotOutcome::Quarantined {
+            key: quarantine_key,
+            rejection,
+        } => Err(SnapshotError::Quarantined {
+            target_key: key.to_string(),
+            quarantine_key,
+            rejection,
+        }),
+    }
+}
+
+/// Store the response to a request without query parameters next to the
+/// snapshot at `key`, gzip-compressed so the reader can send it as stored,
+/// when `policy` compresses documents
+///
+/// Call it after [`replace_snapshot`] succeeded: the reader only sends a
+/// rendered copy written no earlier than the snapshot, so a copy left from
+/// before a quarantine or a run without compression is never served.
+pub async fn write_rendered<R>(
+    store: &dyn DocumentStore,
+    key: &str,
+    render: impl FnOnce() -> R + Send,
+    policy: &SnapshotPolicy,
+) -> Result<(), StorageError>
+where
+    R: Serialize + Sync,
+{
+    if !policy.compressed {
+        return Ok(());
+    }
+    store.put_compressed(&rendered_key(key), &render()).await
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Whether the last `policy.accept_after_rejections` quarantines of `key`,
+/// all since the stored document was written, hold payloads that are valid
+/// and within the allowed change of `candidate`
+async fn confirmed_by_quarantine<T>(
+    store: &dyn DocumentStore,
+    key: &str,
+    candidate: &T,
+    policy: &SnapshotPolicy,
+    stored_at: Option<SystemTime>,
+) -> Result<bool, StorageError>
+where
+    T: Snapshot + DeserializeOwned,
+{
+    let wanted = policy.accept_after_rejections;
+    if wanted == 0 {
+        return Ok(false);
+    }
+    let quarantined = store.list(&quarantine_dir(key)).await?;
+    if quarantined.len() < wanted {
+        return Ok(false);
+    }
+
+    let stored_at = stored_at.map_or(0, unix_seconds);
+    for quarantined_key in quarantined.iter().rev().take(wanted) {
+        let record = match store.get::<QuarantineRecord<T>>(quarantined_key).await {
+            Ok(record) => record,
+            // Not a rejected candidate, such as a corrupt document set aside
+            Err(StorageError::Serialization(_)) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        let agrees = record.rejected_at >= stored_at
+            && record.payload.check().is_ok()
+            && policy.validate(candidate, Some(&record.payload)).is_ok();
+        if !agrees {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Replace the document at `key` with `candidate` if it passes `policy`,
+/// otherwise keep the stored document and quarantine the candidate
+///
+/// A candidate rejected only for its size is still written when the runs
+/// before it were quarantined with payloads like it, see
+/// [`SnapshotPolicy::accept_after_rejections`], so a real change in size
+/// does not keep every later run failing against the old document.
+pub async fn write_snapshot<T>(
+    store: &dyn DocumentStore,
+    key: &str,
+    candidate: &T,
+    policy: &SnapshotPolicy,
+) -> Result<SnapshotOutcome, StorageError>
+where
+    T: Snapshot + Serialize + DeserializeOwned + Sync,
+{
+    let (current, stored_at) = match store.get_modified::<T>(key).await {
+        Ok((current, stored_at)) => (Some(current), stored_at),
+        Err(StorageError::NotFound(_)) | Err(StorageError::Serialization(_)) => (None, None),
+        Err(err) => return Err(err),
+    };
+
+    let accepted = match policy.validate(candidate, current.as_ref()) {
+        Ok(()) => Ok(()),
+        Err(rejection @ Rejection::ChangeTooLarge { .. }) => {
+            if confirmed_by_quarantine(store, key, candidate, policy, stored_at).await? {
+                Ok(())
+            } else {
+                Err(rejection)
+            }
+        }
+        Err(rejection) => Err(rejection),
+    };
+    let rejection = match accepted {
+        Ok(()) => {
+            if policy.compressed {
+                store.put_compressed(key, candidate).await?;
+            } else {
+                store.put(key, candidate).await?;
+            }
+            return Ok(SnapshotOutcome::Written);
+        }
+        Err(rejection) => rejection,
+    };
+
+    let rejected_at = unix_seconds(SystemTime::now());
+    let quarantined = quarantine_key(key, rejected_at);
+    store
+        .put(
+            &quarantined,
+            &QuarantinedSnapshot {
+                target_key: key,
+                reason: rejection.to_string(),
+                rejected_at,
+                payload: candidate,
+            },
+        )
+        .await?;
+
+    Ok(SnapshotOutcome::Quarantined {
+        key: quarantined,
+        rejection,
+    })
+}
+
+#[cfg(all(test, feature = "apimodels"))]
+mod tests {
+    use super::*;
+    use crate::apimodels::{PeopleInSpaceModel, PersonModel};
+    use crate::storage::MemoryStore;
+
+    fn people(count: usize) -> PeopleInSpaceModel {
+        PeopleInSpaceModel {
+            update_time: "2023-11-01 00:00:00 +0000".to_string(),
+            people: (0..count)
+                .map(|n| PersonModel {
+                    name: format!("Astronaut {}", n),
+                    craft: "ISS".to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_payload_is_quarantined_and_stored_document_kept() {
+        let store = MemoryStore::new();
+        let policy = SnapshotPolicy::default();
+
+        let outcome = write_snapshot(&store, "people.json", &people(7), &policy).await;
+        assert_eq!(outcome.unwrap(), SnapshotOutcome::Written);
+
+        let outcome = write_snapshot(&store, "people.json", &people(0), &policy)
+            .await
+            .unwrap();
+        assert!(matches!(
+            outcome,
+            SnapshotOutcome::Quarantined {
+                rejection: Rejection::Empty,
+                ..
+            }
+        ));
+
+        let stored: PeopleInSpaceModel = store.get("people.json").await.unwrap();
+        assert_eq!(stored.people.len(), 7);
+
+        let quarantined = store.list("quarantine/people/").await.unwrap();
+        assert_eq!(quarantined.len(), 1);
+        let record: serde_json::Value = store.get(&quarantined[0]).await.unwrap();
+        assert_eq!(record["target_key"], "people.json");
+        assert_eq!(record["payload"]["people"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn repeated_rejections_of_the_same_size_are_accepted() {
+        let store = MemoryStore::new();
+        let policy = SnapshotPolicy {
+            accept_after_rejections: 2,
+            ..SnapshotPolicy::default()
+        };
+        replace_snapshot(&store, "people.json", &people(7), &policy)
+            .await
+            .unwrap();
+        let rejected = |rejected_at| {
+            let store = &store;
+            async move {
+                store
+                    .put(
+                        &quarantine_key("people.json", rejected_at),
+                        &QuarantinedSnapshot {
+                            target_key: "people.json",
+                            reason: "Size changed".to_string(),
+                            rejected_at,
+                            payload: &people(3),
+                        },
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
+        let now = unix_seconds(SystemTime::now());
+
+        // Rejected before the stored document was written, so they do not count
+        rejected(now - 20).await;
+        rejected(now - 10).await;
+        let outcome = write_snapshot(&store, "people.json", &people(2), &policy)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, SnapshotOutcome::Quarantined { .. }));
+
+        // Together with the run just quarantined that makes two in a row
+        rejected(now + 1).await;
+        let outcome = write_snapshot(&store, "people.json", &people(2), &policy)
+            .await
+            .unwrap();
+        assert_eq!(outcome, SnapshotOutcome::Written);
+        let stored: PeopleInSpaceModel = store.get("people.json").await.unwrap();
+        assert_eq!(stored.people.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rendered_copies_are_only_written_for_compressed_stores() {
+        let store = MemoryStore::new();
+        let policy = SnapshotPolicy::default();
+        write_rendered(&store, "people.json", || people(7), &policy)
+            .await
+            .unwrap();
+        assert!(store.list("rendered/").await.unwrap().is_empty());
+
+        let policy = SnapshotPolicy {
+            compressed: true,
+            ..policy
+        };
+        write_rendered(&store, "people.json", || people(7), &policy)
+            .await
+            .unwrap();
+        let rendered = store.get_bytes("rendered/people.json").await.unwrap();
+        assert!(crate::storage::is_gzip(&rendered));
+    }
+
+    #[tokio::test]
+    async fn replace_snapshot_reports_the_quarantine() {
+        let store = MemoryStore::new();
+        let policy = SnapshotPolicy::default();
+
+        replace_snapshot(&store, "people.json", &people(7), &policy)
+            .await
+            .unwrap();
+        let err = replace_snapshot(&store, "people.json", &people(1), &policy)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            SnapshotError::Quarantined {
+                rejection: Rejection::ChangeTooLarge { .. },
+                ..
+            }
+        ));
+        assert!(err
+            .to_string()
+            .starts_with("Kept the stored people.json: Size changed from 7 to 1"));
+    }
+}